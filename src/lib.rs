@@ -0,0 +1,3514 @@
+//! BurnEngine core: USB drive detection, image inspection, the dd write
+//! and the MD5 readback, without any terminal UI.
+//!
+//! The `burn` CLI is a thin layer over this crate; other tools can embed it
+//! and report progress through the [`Progress`] trait.
+
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::{ChildStderr, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ─────────────────────────────────────────────
+//  PROGRESS
+// ─────────────────────────────────────────────
+
+/// Progress callbacks for long-running operations (write, readback).
+/// Both methods default to doing nothing.
+pub trait Progress: Send + Sync {
+    /// An operation of `total` bytes is starting (or restarting after a retry)
+    fn start(&self, _total: u64) {}
+    /// `done` bytes processed so far
+    fn bytes(&self, _done: u64) {}
+    /// The write has been waiting on the source for a while (true) or has
+    /// data again (false) — the source, not the device, is the bottleneck
+    fn waiting_on_source(&self, _waiting: bool) {}
+}
+
+/// Ignores all progress
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
+
+// ─────────────────────────────────────────────
+//  ERRORS
+// ─────────────────────────────────────────────
+
+/// Everything the library returns fails with a [`BurnError`]
+pub type Result<T, E = BurnError> = std::result::Result<T, E>;
+
+/// The library's error. The named variants are the failures a caller may
+/// want to tell apart; `Context` and `Other` carry everything else.
+/// [`BurnError::kind`] looks through the context to the variant that matters.
+#[derive(Debug, thiserror::Error)]
+pub enum BurnError {
+    /// Not a single removable USB drive was detected
+    #[error(
+        "No USB drives detected!\n\
+         • Make sure the USB is plugged in\n\
+         • Run `burn doctor` to see why each disk was skipped"
+    )]
+    NoDevicesFound,
+    /// The image (bytes) doesn't fit on the device (bytes)
+    #[error("Image ({}) is LARGER than the USB ({})!", format_size(*.iso), format_size(*.device))]
+    DeviceTooSmall { iso: u64, device: u64 },
+    /// dd failed; its exit code, if it exited at all, and what it said
+    #[error("dd exited with code {}", .code.unwrap_or(-1))]
+    WriteFailed {
+        code: Option<i32>,
+        #[source]
+        dd: DdError,
+    },
+    /// The USB was read back completely but its hash differs from the image's
+    #[error(
+        "Data mismatch: the USB was read back completely but its hash differs \
+         (image {iso_hash}, USB {device_hash}) — write may have failed or USB is faulty"
+    )]
+    VerifyMismatch { iso_hash: String, device_hash: String },
+    /// The device (path) couldn't be opened for lack of privileges
+    #[error("Permission denied opening {path} — run as root (sudo burn …)")]
+    PermissionDenied { path: String },
+    /// The user said no at a prompt
+    #[error("Cancelled")]
+    Cancelled,
+    /// What was being done when `source` failed
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    /// A failure with nothing more specific to say than its message
+    #[error("{0}")]
+    Other(String),
+}
+
+impl BurnError {
+    /// The innermost named variant under any `Context`, or the outermost
+    /// error if there is none
+    pub fn kind(&self) -> &BurnError {
+        let mut error = self;
+        while let BurnError::Context { source, .. } = error {
+            match source.downcast_ref::<BurnError>() {
+                Some(inner) => error = inner,
+                None => break,
+            }
+        }
+        error
+    }
+
+    /// The message followed by each cause's, `: `-separated
+    pub fn report(&self) -> String {
+        let mut text = self.to_string();
+        let mut cause = std::error::Error::source(self);
+        while let Some(e) = cause {
+            text.push_str(": ");
+            text.push_str(&e.to_string());
+            cause = e.source();
+        }
+        text
+    }
+}
+
+/// `BurnError::Other` from a format string
+macro_rules! burn_err {
+    ($($arg:tt)*) => {
+        BurnError::Other(format!($($arg)*))
+    };
+}
+
+/// `.context()` / `.with_context()` on anything that fails, wrapping the
+/// failure in `BurnError::Context`
+trait Context<T> {
+    fn context<C: std::fmt::Display>(self, context: C) -> Result<T>;
+    fn with_context<C: std::fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T, E: std::error::Error + Send + Sync + 'static> Context<T> for std::result::Result<T, E> {
+    fn context<C: std::fmt::Display>(self, context: C) -> Result<T> {
+        self.with_context(|| context)
+    }
+
+    fn with_context<C: std::fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|e| BurnError::Context {
+            context: f().to_string(),
+            source: Box::new(e),
+        })
+    }
+}
+
+// ─────────────────────────────────────────────
+//  USB DEVICE
+// ─────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+pub struct UsbDevice {
+    /// e.g. "sdb"
+    pub name: String,
+    /// e.g. "/dev/sdb"
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Size the kernel reported — only differs from `size` under --assume-device-size
+    pub reported_size: u64,
+    /// Vendor/Model from sysfs
+    pub model: String,
+    /// USB serial number, if the stick reports one
+    pub serial: Option<String>,
+    /// Is it actually removable?
+    pub removable: bool,
+    /// Transport: usb / ata / nvme etc.
+    pub transport: String,
+    /// Kernel "major:minor" from sysfs when the device was scanned, e.g. "8:16"
+    pub dev_id: Option<String>,
+    /// Logical sector size in bytes — 512, or 4096 behind some USB bridges
+    pub logical_sector_size: u64,
+    /// Stable /dev/disk/by-id/… link to the whole disk, if udev made one
+    pub by_id: Option<String>,
+}
+
+impl UsbDevice {
+    /// Stand-in "device" for `--target-file` testing runs
+    pub fn file_target(path: &Path, size: u64) -> Self {
+        UsbDevice {
+            name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            path: path.display().to_string(),
+            size,
+            reported_size: size,
+            model: "Regular file (testing)".to_string(),
+            serial: None,
+            removable: false,
+            transport: "file".to_string(),
+            dev_id: None,
+            logical_sector_size: 512,
+            by_id: None,
+        }
+    }
+
+
+    pub fn is_file_target(&self) -> bool {
+        self.transport == "file"
+    }
+
+    /// Device size in the units chosen with [`set_units`]
+    pub fn size_human(&self) -> String {
+        format_size(self.size)
+    }
+}
+
+/// How sizes are shown: SI (GB, how vendors label sticks) or IEC (GiB,
+/// what the kernel and partitioning tools report)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Units {
+    #[default]
+    Si,
+    Iec,
+}
+
+impl Units {
+    /// "14.9 GB" / "13.9 GiB"; below one G-unit, whole MB / MiB
+    pub fn format(self, bytes: u64) -> String {
+        let (k, g, m) = match self {
+            Units::Si => (1e3, "GB", "MB"),
+            Units::Iec => (1024.0, "GiB", "MiB"),
+        };
+        let gigs = bytes as f64 / (k * k * k);
+        if gigs >= 1.0 {
+            format!("{:.1} {}", gigs, g)
+        } else {
+            format!("{:.0} {}", bytes as f64 / (k * k), m)
+        }
+    }
+}
+
+static UNITS: OnceLock<Units> = OnceLock::new();
+
+/// Pick the units for every size shown; only the first call counts
+pub fn set_units(units: Units) {
+    let _ = UNITS.set(units);
+}
+
+pub fn units() -> Units {
+    UNITS.get().copied().unwrap_or_default()
+}
+
+/// `bytes` in the chosen units
+pub fn format_size(bytes: u64) -> String {
+    units().format(bytes)
+}
+
+// ─────────────────────────────────────────────
+//  DETECT USB DRIVES (SAFE)
+// ─────────────────────────────────────────────
+
+/// Read a sysfs file as trimmed string
+fn sysfs_read(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Everything device detection reads from the system. [`RealSysfs`] is the
+/// live tree; another implementation can feed detection a synthetic one.
+pub trait SysfsProvider: Send + Sync {
+    /// Entry names in /sys/block (sda, sdb, loop0…)
+    fn list_block(&self) -> Vec<String>;
+    /// Trimmed /sys/block/<name>/<attr>; `attr` may be nested ("queue/rotational")
+    fn read_attr(&self, name: &str, attr: &str) -> Option<String> {
+        self.read_file(&Path::new("/sys/block").join(name).join(attr))
+    }
+    /// Trimmed contents of any sysfs file
+    fn read_file(&self, path: &Path) -> Option<String>;
+    /// Target of a symlink (as written, not resolved)
+    fn read_link(&self, path: &Path) -> Option<PathBuf>;
+    /// Path with every symlink resolved
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf>;
+    /// True if the /dev node exists
+    fn dev_node_exists(&self, dev_path: &str) -> bool;
+    /// "major:minor" of a block device node, None if it isn't one
+    fn dev_node_id(&self, dev_path: &str) -> Option<String>;
+    /// Entry names in /dev/disk/by-id
+    fn list_by_id(&self) -> Vec<String>;
+}
+
+/// The running kernel's /sys and /dev
+pub struct RealSysfs;
+
+impl SysfsProvider for RealSysfs {
+    fn list_block(&self) -> Vec<String> {
+        fs::read_dir("/sys/block")
+            .map(|d| d.flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    fn read_file(&self, path: &Path) -> Option<String> {
+        sysfs_read(&path.to_string_lossy())
+    }
+
+    fn read_link(&self, path: &Path) -> Option<PathBuf> {
+        fs::read_link(path).ok()
+    }
+
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf> {
+        fs::canonicalize(path).ok()
+    }
+
+    fn dev_node_exists(&self, dev_path: &str) -> bool {
+        Path::new(dev_path).exists()
+    }
+
+    /// From the node's st_rdev
+    fn dev_node_id(&self, dev_path: &str) -> Option<String> {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+        let meta = fs::metadata(dev_path).ok()?;
+        if !meta.file_type().is_block_device() {
+            return None;
+        }
+        let rdev = meta.rdev();
+        let major = ((rdev >> 8) & 0xfff) | ((rdev >> 32) & 0xffff_f000);
+        let minor = (rdev & 0xff) | ((rdev >> 12) & 0xffff_ff00);
+        Some(format!("{}:{}", major, minor))
+    }
+
+    fn list_by_id(&self) -> Vec<String> {
+        fs::read_dir(BY_ID_DIR)
+            .map(|d| d.flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// udev's persistent names, stable across reboots and replugging
+pub const BY_ID_DIR: &str = "/dev/disk/by-id";
+
+/// The by-id link that resolves to /dev/<name> — "usb-…" preferred over
+/// "wwn-…" and the like, partition links ("…-partN") never
+fn find_by_id(sys: &dyn SysfsProvider, name: &str) -> Option<String> {
+    let node = PathBuf::from(format!("/dev/{}", name));
+    let mut links: Vec<String> = sys.list_by_id()
+        .into_iter()
+        .filter(|l| !l.contains("-part"))
+        .filter(|l| sys.canonicalize(&Path::new(BY_ID_DIR).join(l)).as_ref() == Some(&node))
+        .collect();
+    links.sort_by_key(|l| (!l.starts_with("usb-"), l.clone()));
+    links.first().map(|l| format!("{}/{}", BY_ID_DIR, l))
+}
+
+/// Smallest drive offered as a target — anything below is a card reader slot or junk.
+/// This floor is for devices only: an image of any size (a 30 MB netboot ISO)
+/// can go onto a drive above it.
+pub const MIN_USB_BYTES: u64 = 100_000_000;
+
+/// Why a /sys/block entry is never offered as a write target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exclusion {
+    /// loop, ram, zram, dm-*, md*
+    Virtual,
+    /// sr* — CD/DVD drive
+    Optical,
+    /// Internal disk (removable=0)
+    NotRemovable,
+    /// Not on the USB bus (ata, nvme, mmc…)
+    Transport(String),
+    NoDevNode,
+    /// Empty or tiny (< MIN_USB_BYTES)
+    TooSmall(u64),
+    /// Not on the configured allow-list
+    NotAllowed,
+}
+
+impl Exclusion {
+    pub fn describe(&self) -> String {
+        match self {
+            Exclusion::Virtual => "virtual device (loop/ram/zram/dm/md)".into(),
+            Exclusion::Optical => "optical drive".into(),
+            Exclusion::NotRemovable => "not removable (removable=0) — internal disk".into(),
+            Exclusion::Transport(t) => format!("transport={} — not on the USB bus", t),
+            Exclusion::NoDevNode => "no /dev node".into(),
+            Exclusion::TooSmall(0) => "empty (no medium inserted?)".into(),
+            Exclusion::TooSmall(n) => format!("too small ({} bytes < {} MB drive minimum)", n, MIN_USB_BYTES / 1_000_000),
+            Exclusion::NotAllowed => "not in the allowed_devices policy".into(),
+        }
+    }
+}
+
+/// The safety filter, one check at a time: None = a removable USB drive
+pub fn exclusion_reason(sys: &dyn SysfsProvider, dev: &UsbDevice) -> Option<Exclusion> {
+    let name = dev.name.as_str();
+    if ["loop", "ram", "zram", "dm-", "md"].iter().any(|p| name.starts_with(p)) {
+        return Some(Exclusion::Virtual);
+    }
+    if name.starts_with("sr") {
+        return Some(Exclusion::Optical);
+    }
+    // ── SAFETY CHECK 1: Must be removable ──
+    if !dev.removable {
+        return Some(Exclusion::NotRemovable); // skip internal disks!
+    }
+    // ── SAFETY CHECK 2: Transport must be usb ──
+    if dev.transport != "usb" {
+        return Some(Exclusion::Transport(dev.transport.clone())); // skip eSATA, SD cards via wrong path, etc.
+    }
+    // ── SAFETY CHECK 3: Must have a /dev node ──
+    if !sys.dev_node_exists(&dev.path) {
+        return Some(Exclusion::NoDevNode);
+    }
+    if dev.size < MIN_USB_BYTES {
+        return Some(Exclusion::TooSmall(dev.size));
+    }
+    // ── SAFETY CHECK 4: Site policy, if any ──
+    if !is_allowed(sys, dev) {
+        return Some(Exclusion::NotAllowed);
+    }
+    None
+}
+
+static ALLOWED_DEVICES: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Restrict every scan to these drives: by-id (or other /dev/disk) links,
+/// bare by-id names, or serials. Empty = no restriction; only the first call counts.
+pub fn set_allowed_devices(list: Vec<String>) {
+    let _ = ALLOWED_DEVICES.set(list);
+}
+
+/// The allow-list in force (empty = any USB drive)
+pub fn allowed_devices() -> &'static [String] {
+    ALLOWED_DEVICES.get().map_or(&[], Vec::as_slice)
+}
+
+fn is_allowed(sys: &dyn SysfsProvider, dev: &UsbDevice) -> bool {
+    let list = allowed_devices();
+    list.is_empty() || list.iter().any(|entry| {
+        let link = if entry.starts_with('/') { PathBuf::from(entry) } else { Path::new(BY_ID_DIR).join(entry) };
+        dev.serial.as_deref() == Some(entry.as_str())
+            || sys.canonicalize(&link).is_some_and(|p| p == Path::new(&dev.path))
+    })
+}
+
+/// Where a scan spent its time — for `--verbose` on machines with many LUNs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DetectTimings {
+    pub block_devices: usize,
+    pub threads: usize,
+    /// Listing /sys/block
+    pub enumerate: Duration,
+    /// Attributes, by-id links and the safety filter (summed over threads)
+    pub sysfs_reads: Duration,
+    /// Walking each device up to its bus (summed over threads)
+    pub transport: Duration,
+    pub total: Duration,
+}
+
+/// Below this many /sys/block entries the probes run one after another
+const PARALLEL_PROBE_MIN: usize = 16;
+/// Upper bound on probe threads
+const PROBE_THREADS: usize = 8;
+
+/// Detect ONLY removable USB block devices (whole disks, not partitions)
+pub fn detect_usb_drives(sys: &dyn SysfsProvider) -> Vec<UsbDevice> {
+    detect_usb_drives_timed(sys).0
+}
+
+/// [`detect_usb_drives`], plus where the time went. Large machines are probed
+/// on a few threads; the result keeps /sys/block's order either way.
+pub fn detect_usb_drives_timed(sys: &dyn SysfsProvider) -> (Vec<UsbDevice>, DetectTimings) {
+    let started = Instant::now();
+    let names = sys.list_block();
+    let mut timings = DetectTimings {
+        block_devices: names.len(),
+        threads: 1,
+        enumerate: started.elapsed(),
+        ..Default::default()
+    };
+
+    let probe = |name: &String| {
+        let (dev, mut reads, transport) = probe_block_device_timed(sys, name);
+        let filtering = Instant::now();
+        let why = exclusion_reason(sys, &dev);
+        reads += filtering.elapsed();
+        (dev, why, reads, transport)
+    };
+    let probed: Vec<_> = if names.len() < PARALLEL_PROBE_MIN {
+        names.iter().map(probe).collect()
+    } else {
+        timings.threads = thread::available_parallelism().map_or(4, |n| n.get()).min(PROBE_THREADS);
+        let per_thread = names.len().div_ceil(timings.threads);
+        let probe = &probe;
+        thread::scope(|scope| {
+            let workers: Vec<_> = names
+                .chunks(per_thread)
+                .map(|chunk| scope.spawn(move || chunk.iter().map(probe).collect::<Vec<_>>()))
+                .collect();
+            // Joined in spawn order, so the list comes out in /sys/block order
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        })
+    };
+
+    let mut devices = Vec::new();
+    for (dev, why, reads, transport) in probed {
+        timings.sysfs_reads += reads;
+        timings.transport += transport;
+        if let Some(why) = why {
+            log::trace!("Skipping {}: {}", dev.path, why.describe());
+            continue;
+        }
+
+        log::debug!(
+            "Detected USB drive {} ({} bytes, model '{}', transport {})",
+            dev.path, dev.size, dev.model, dev.transport
+        );
+
+        devices.push(dev);
+    }
+
+    timings.total = started.elapsed();
+    log::debug!("{:?}", timings);
+    (devices, timings)
+}
+
+/// Read what sysfs says about /sys/block/<name> — no safety filtering
+pub fn probe_block_device(sys: &dyn SysfsProvider, name: &str) -> UsbDevice {
+    probe_block_device_timed(sys, name).0
+}
+
+/// The device, the time spent on sysfs reads, and on transport resolution
+fn probe_block_device_timed(sys: &dyn SysfsProvider, name: &str) -> (UsbDevice, Duration, Duration) {
+    let started = Instant::now();
+    let sys_path = format!("/sys/block/{}", name);
+
+    let resolving = Instant::now();
+    let transport = detect_transport(sys, &sys_path);
+    let transport_time = resolving.elapsed();
+
+    let removable = sys.read_attr(name, "removable")
+        .map(|s| s == "1")
+        .unwrap_or(false);
+
+    // Size in bytes (size file gives 512-byte sectors)
+    let size_sectors: u64 = sys.read_attr(name, "size")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+
+    let dev = UsbDevice {
+        name: name.to_string(),
+        path: format!("/dev/{}", name),
+        size: size_sectors * 512,
+        reported_size: size_sectors * 512,
+        // Model + serial from sysfs
+        model: read_model(sys, &sys_path),
+        serial: read_serial(sys, &sys_path),
+        removable,
+        transport,
+        dev_id: sys.read_attr(name, "dev"),
+        // sysfs "size" stays in 512-byte units either way; this is what the bridge presents
+        logical_sector_size: sys.read_attr(name, "queue/logical_block_size")
+            .and_then(|s| s.parse().ok())
+            .filter(|n: &u64| n.is_power_of_two() && *n >= 512)
+            .unwrap_or(512),
+        by_id: find_by_id(sys, name),
+    };
+    (dev, started.elapsed().saturating_sub(transport_time), transport_time)
+}
+
+/// Every entry in /sys/block, sorted — diagnostics only, never a write target
+pub fn detect_all_block_devices(sys: &dyn SysfsProvider) -> Vec<UsbDevice> {
+    let mut all: Vec<UsbDevice> = sys.list_block()
+        .iter()
+        .map(|name| probe_block_device(sys, name))
+        .collect();
+    all.sort_by(|a, b| a.name.cmp(&b.name));
+    all
+}
+
+/// Read the model string from sysfs, falling back to a generic name
+pub fn read_model(sys: &dyn SysfsProvider, sys_path: &str) -> String {
+    read_device_model(sys, sys_path).unwrap_or_else(|| "USB Drive".to_string())
+}
+
+/// Human name of a drive, tried in order: the SCSI `vendor` + `model` pair
+/// (USB mass storage and UAS), then the USB device's `manufacturer` +
+/// `product` a few levels above.
+pub fn read_device_model(sys: &dyn SysfsProvider, sys_path: &str) -> Option<String> {
+    let read = |p: &Path| sys.read_file(p).filter(|s| !s.is_empty());
+    let device = PathBuf::from(format!("{}/device", sys_path));
+
+    if let Some(model) = read(&device.join("model")) {
+        // "ATA" is libata's placeholder, not a vendor
+        let vendor = read(&device.join("vendor")).filter(|v| v != "ATA");
+        return Some(join_model(vendor, model));
+    }
+
+    // The USB device sits a few directories up: scsi device → target → host
+    // → interface → device
+    let real = sys.canonicalize(&device)?;
+    real.ancestors()
+        .take(6)
+        .find_map(|d| read(&d.join("product")).map(|p| join_model(read(&d.join("manufacturer")), p)))
+}
+
+/// "vendor model" with runs of whitespace collapsed, and without repeating a
+/// vendor the model already starts with
+fn join_model(vendor: Option<String>, model: String) -> String {
+    let model = model.split_whitespace().collect::<Vec<_>>().join(" ");
+    match vendor.map(|v| v.split_whitespace().collect::<Vec<_>>().join(" ")) {
+        Some(v) if !v.is_empty() && !model.to_lowercase().starts_with(&v.to_lowercase()) => {
+            format!("{} {}", v, model)
+        }
+        _ => model,
+    }
+}
+
+/// USB serial number: the first `serial` attribute above `<sys_path>/device`
+pub fn read_serial(sys: &dyn SysfsProvider, sys_path: &str) -> Option<String> {
+    let real = sys.canonicalize(Path::new(&format!("{}/device", sys_path)))?;
+    real.ancestors()
+        .take(6)
+        .find_map(|d| sys.read_file(&d.join("serial")))
+        .filter(|s| !s.is_empty())
+}
+
+/// One sysfs scan per command, shared by everything that needs the device list
+/// so selection and validation always see the same set of drives
+pub struct DeviceScanner {
+    cache: std::cell::OnceCell<Vec<UsbDevice>>,
+    /// Called with the timings once the scan has run
+    on_scan: Option<fn(&DetectTimings)>,
+}
+
+impl Default for DeviceScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceScanner {
+    pub fn new() -> Self {
+        DeviceScanner { cache: std::cell::OnceCell::new(), on_scan: None }
+    }
+
+    /// Hand the scan's timings to `report` when it runs
+    pub fn report_timings(mut self, report: fn(&DetectTimings)) -> Self {
+        self.on_scan = Some(report);
+        self
+    }
+
+    /// A scanner that reports exactly these devices (no sysfs scan)
+    pub fn from_devices(devices: Vec<UsbDevice>) -> Self {
+        DeviceScanner { cache: std::cell::OnceCell::from(devices), on_scan: None }
+    }
+
+    /// Scan on first use, then return the same list
+    pub fn devices(&self) -> &[UsbDevice] {
+        self.cache.get_or_init(|| {
+            let (devices, timings) = detect_usb_drives_timed(&RealSysfs);
+            if let Some(report) = self.on_scan {
+                report(&timings);
+            }
+            devices
+        })
+    }
+}
+
+/// Re-check that the device node still refers to the drive the user selected.
+///
+/// Guards against a stick being swapped between selection and write.
+pub fn revalidate_device(sys: &dyn SysfsProvider, device: &UsbDevice) -> Result<()> {
+    if device.is_file_target() {
+        return Ok(());
+    }
+    let sys_path = format!("/sys/block/{}", device.name);
+    let changed = |why: String| {
+        burn_err!(
+            "Device changed since selection, aborting for safety.\n\
+             {} ({})\n\
+             Re-run the command to select the drive again.",
+            device.path, why
+        )
+    };
+
+    if !sys.dev_node_exists(&device.path) {
+        return Err(changed("device node no longer exists".into()));
+    }
+
+    let size = sys.read_attr(&device.name, "size")
+        .and_then(|s| s.parse::<u64>().ok())
+        .map(|sectors| sectors * 512)
+        .ok_or_else(|| changed("cannot read size from sysfs".into()))?;
+    if size != device.reported_size {
+        return Err(changed(format!("size was {} bytes, now {}", device.reported_size, size)));
+    }
+
+    let model = read_model(sys, &sys_path);
+    if model != device.model {
+        return Err(changed(format!("model was '{}', now '{}'", device.model, model)));
+    }
+
+    // The numbers the kernel actually opens: both sysfs and the /dev node
+    // must still agree with what was scanned
+    if let Some(expected) = &device.dev_id {
+        let now = sys.read_attr(&device.name, "dev");
+        if now.as_deref() != Some(expected.as_str()) {
+            return Err(changed(format!(
+                "major:minor was {}, now {}",
+                expected, now.as_deref().unwrap_or("unknown")
+            )));
+        }
+        let node = sys.dev_node_id(&device.path);
+        if node.as_deref() != Some(expected.as_str()) {
+            return Err(changed(format!(
+                "{} is {} but the scanned drive was {}",
+                device.path, node.as_deref().unwrap_or("not a device node"), expected
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// True if `path` is a block device node (S_IFBLK) — not a regular file or
+/// anything else that happens to sit under /dev
+pub fn is_block_device(path: &str) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    fs::metadata(path).is_ok_and(|m| m.file_type().is_block_device())
+}
+
+/// What the device can really be read up to: the end offset of the node
+/// itself, whatever size the scan (or --assume-device-size) went by
+pub fn device_real_size(device: &UsbDevice) -> Result<u64> {
+    use std::io::{Seek, SeekFrom};
+    let mut f = fs::File::open(&device.path)
+        .with_context(|| format!("Cannot open {} to probe its size", device.path))?;
+    f.seek(SeekFrom::End(0))
+        .with_context(|| format!("Cannot probe the size of {}", device.path))
+}
+
+/// True if the stick is read-only (lock switch): sysfs `ro` flag or EROFS on open/write
+pub fn is_write_protected(device: &UsbDevice) -> bool {
+    use std::io::Write;
+    if device.is_file_target() {
+        return false;
+    }
+    if is_kernel_read_only(device) {
+        return true;
+    }
+    const EROFS: i32 = 30;
+    match fs::OpenOptions::new().write(true).open(&device.path) {
+        Ok(mut f) => matches!(f.write(&[]), Err(e) if e.raw_os_error() == Some(EROFS)),
+        Err(e) => e.raw_os_error() == Some(EROFS),
+    }
+}
+
+/// The kernel's own read-only flag (sysfs `ro`) — set by a lock switch, or by
+/// `blockdev --setro` until the device is replugged
+pub fn is_kernel_read_only(device: &UsbDevice) -> bool {
+    sysfs_read(&format!("/sys/block/{}/ro", device.name)).as_deref() == Some("1")
+}
+
+/// Set or clear the kernel read-only flag with `blockdev --setro/--setrw`.
+/// It only lasts until the device is replugged or the machine reboots.
+pub fn set_read_only(device: &UsbDevice, read_only: bool) -> Result<()> {
+    let flag = if read_only { "--setro" } else { "--setrw" };
+    let out = Command::new("blockdev")
+        .arg(flag)
+        .arg(&device.path)
+        .output()
+        .context("Failed to run blockdev")?;
+    if !out.status.success() {
+        return Err(burn_err!(
+            "blockdev {} {} failed: {}",
+            flag,
+            device.path,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    log::info!("blockdev {} {}", flag, device.path);
+    Ok(())
+}
+
+/// Walk sysfs to find transport type (usb / ata / nvme / mmc)
+///
+/// Follows `<sys_path>/device` and checks the `subsystem` link of every
+/// ancestor, so UAS drives (…/usb…/host/target/scsi…) and nested hubs are
+/// still reported as "usb".
+pub fn detect_transport(sys: &dyn SysfsProvider, sys_path: &str) -> String {
+    let device_link = format!("{}/device", sys_path);
+    let real = match sys.canonicalize(Path::new(&device_link)) {
+        Some(r) => r,
+        None => return "unknown".to_string(),
+    };
+
+    let mut found: Option<&str> = None;
+    let mut dir = Some(real.as_path());
+    while let Some(d) = dir {
+        let subsystem = sys.read_link(&d.join("subsystem"));
+        match subsystem.as_deref().and_then(|s| s.file_name()).and_then(|s| s.to_str()) {
+            Some("usb") => return "usb".to_string(),
+            Some("nvme") => { found.get_or_insert("nvme"); }
+            Some("mmc") => { found.get_or_insert("mmc"); }
+            _ => {}
+        }
+        dir = d.parent();
+    }
+    if let Some(t) = found {
+        return t.to_string();
+    }
+
+    // No subsystem links (unusual kernels) — fall back to the path itself
+    let real_str = real.to_string_lossy();
+    if real_str.contains("/usb") {
+        return "usb".to_string();
+    }
+    if real_str.contains("nvme") {
+        return "nvme".to_string();
+    }
+    if real_str.contains("mmc") {
+        return "mmc".to_string();
+    }
+    if real_str.contains("ata") {
+        return "ata".to_string();
+    }
+    "unknown".to_string()
+}
+
+// ─────────────────────────────────────────────
+//  UNMOUNT PARTITIONS
+// ─────────────────────────────────────────────
+
+/// Mounts of the device itself or any of its partitions → (source, mountpoint)
+pub fn device_mounts(device: &UsbDevice) -> Vec<(String, String)> {
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+    mounts.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let dev = parts.next()?;
+            // /proc/mounts escapes spaces etc. as octal (\040)
+            let point = parts.next()?.replace("\\040", " ").replace("\\011", "\t");
+            let rest = dev.strip_prefix(device.path.as_str())?;
+            // sdb, sdb1, mmcblk0p1 — but not sdbb
+            let rest = rest.strip_prefix('p').unwrap_or(rest);
+            rest.chars().all(|c| c.is_ascii_digit())
+                .then(|| (dev.to_string(), point))
+        })
+        .collect()
+}
+
+/// A filesystem lsblk found on the device or one of its partitions
+#[derive(Debug, Clone)]
+pub struct FsInfo {
+    /// e.g. "sdb1"
+    pub name: String,
+    /// e.g. "vfat", "ext4", "iso9660"
+    pub fstype: String,
+    pub label: Option<String>,
+    /// Only known while mounted
+    pub used: Option<u64>,
+}
+
+/// Filesystems currently on the device, via `lsblk -P`.
+/// None if lsblk is missing or fails; an empty list means no filesystems.
+pub fn device_filesystems(device: &UsbDevice) -> Option<Vec<FsInfo>> {
+    let out = Command::new("lsblk")
+        .args(["-P", "-b", "-o", "NAME,FSTYPE,LABEL,FSUSED", &device.path])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&out.stdout);
+    Some(text.lines().filter_map(|line| {
+        let fields = lsblk_pairs(line);
+        let get = |k: &str| fields.iter()
+            .find(|(key, _)| key == k)
+            .map(|(_, v)| v.clone())
+            .filter(|v| !v.is_empty());
+        Some(FsInfo {
+            name: get("NAME")?,
+            fstype: get("FSTYPE")?,
+            label: get("LABEL"),
+            used: get("FSUSED").and_then(|v| v.parse().ok()),
+        })
+    }).collect())
+}
+
+/// `KEY="value" KEY2="..."` → pairs, decoding lsblk's `\xNN` escapes
+fn lsblk_pairs(line: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut rest = line.trim();
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq].trim().to_string();
+        let after = &rest[eq + 2..];
+        let Some(end) = after.find('"') else { break };
+        let raw = &after.as_bytes()[..end];
+        let mut value = Vec::with_capacity(raw.len());
+        let mut i = 0;
+        while i < raw.len() {
+            let hex = raw.get(i + 2..i + 4)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            match hex {
+                Some(b) if raw[i..].starts_with(b"\\x") => {
+                    value.push(b);
+                    i += 4;
+                }
+                _ => {
+                    value.push(raw[i]);
+                    i += 1;
+                }
+            }
+        }
+        let value = String::from_utf8_lossy(&value).to_string();
+        pairs.push((key, value));
+        rest = &after[end + 1..];
+    }
+    pairs
+}
+
+/// Unmount everything on the device; retries busy mounts lazily (umount -l).
+/// With `dry_run` nothing is unmounted — the mounts are only logged.
+/// `each(source, mountpoint, false)` is called just before each one is
+/// unmounted and `each(…, true)` once it is — so a caller knows what's gone
+/// even if a later one fails. → the (source, mountpoint) pairs that were unmounted
+pub fn unmount_device(
+    device: &UsbDevice,
+    dry_run: bool,
+    each: &mut dyn FnMut(&str, &str, bool),
+) -> Result<Vec<(String, String)>> {
+    // Deepest mountpoints first so nested mounts don't keep parents busy
+    let mut mounts = device_mounts(device);
+    mounts.sort_by_key(|m| std::cmp::Reverse(m.1.len()));
+    if dry_run {
+        for (dev, point) in &mounts {
+            log::info!("dry-run: would unmount {} ({})", point, dev);
+        }
+        return Ok(Vec::new());
+    }
+
+    let mut unmounted = Vec::new();
+    for (dev, point) in &mounts {
+        log::info!("Unmounting {} ({})", point, dev);
+        each(dev, point, false);
+        let ok = Command::new("umount").arg(point).status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        log::info!("umount {} → {}", point, ok);
+        let ok = ok || {
+            log::warn!("{} is busy — retrying with a lazy unmount", point);
+            let lazy = Command::new("umount").args(["-l", point.as_str()]).status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+            log::info!("umount -l {} → {}", point, lazy);
+            lazy
+        };
+        if ok {
+            each(dev, point, true);
+            unmounted.push((dev.clone(), point.clone()));
+        }
+    }
+
+    let still: Vec<String> = device_mounts(device)
+        .into_iter()
+        .map(|(dev, point)| format!("  • {} on {}", dev, point))
+        .collect();
+    if !still.is_empty() {
+        return Err(burn_err!(
+            "Could not unmount {} — still mounted:\n{}\n\
+             Close any programs using the drive (file managers, terminals) and try again.",
+            device.path,
+            still.join("\n")
+        ));
+    }
+    Ok(unmounted)
+}
+
+// ─────────────────────────────────────────────
+//  RAID / LVM MEMBERSHIP
+// ─────────────────────────────────────────────
+
+/// mdadm superblock magic (0xa92b4efc, little-endian on disk)
+const MD_MAGIC: [u8; 4] = [0xfc, 0x4e, 0x2b, 0xa9];
+/// LVM physical volume label, in one of the first four sectors
+const LVM_LABEL: &[u8] = b"LABELONE";
+
+/// Why the device looks like part of an md RAID array or an LVM volume
+/// group — one line per finding, empty when it looks like a plain stick.
+/// Checks `/proc/mdstat`, the sysfs holders of the disk and its partitions,
+/// and the md / LVM superblocks themselves (those catch arrays that aren't
+/// assembled right now).
+pub fn raid_lvm_membership(device: &UsbDevice) -> Vec<String> {
+    if device.is_file_target() {
+        return Vec::new();
+    }
+    let mut found = Vec::new();
+    let name = device.name.as_str();
+    let sys_path = format!("/sys/block/{}", name);
+
+    // The disk itself, then its partitions: (name, size in bytes)
+    let mut members = vec![(name.to_string(), device.size)];
+    if let Ok(entries) = fs::read_dir(&sys_path) {
+        for e in entries.flatten() {
+            let part = e.file_name().to_string_lossy().to_string();
+            if part.starts_with(name) && Path::new(&format!("{}/{}/partition", sys_path, part)).exists() {
+                let sectors: u64 = sysfs_read(&format!("{}/{}/size", sys_path, part))
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0);
+                members.push((part, sectors * 512));
+            }
+        }
+    }
+    members.sort();
+
+    if let Ok(mdstat) = fs::read_to_string("/proc/mdstat") {
+        for line in mdstat.lines() {
+            let Some((md, rest)) = line.split_once(" : ") else { continue };
+            // "md0 : active raid1 sdb1[0] sdc1[1]"
+            let hit = rest.split_whitespace()
+                .filter_map(|t| t.split('[').next())
+                .find(|t| members.iter().any(|(m, _)| m == t));
+            if let Some(member) = hit {
+                found.push(format!("/dev/{} is a member of /dev/{} (/proc/mdstat)", member, md.trim()));
+            }
+        }
+    }
+
+    for (member, bytes) in &members {
+        let dir = if member == name { sys_path.clone() } else { format!("{}/{}", sys_path, member) };
+        if let Ok(holders) = fs::read_dir(format!("{}/holders", dir)) {
+            for h in holders.flatten() {
+                let holder = h.file_name().to_string_lossy().to_string();
+                let what = if holder.starts_with("md") { "RAID array" } else { "device-mapper / LVM volume" };
+                let line = format!("/dev/{} is in use by /dev/{} ({})", member, holder, what);
+                if !found.iter().any(|f| f.contains(&format!("/dev/{} ", holder))) {
+                    found.push(line);
+                }
+            }
+        }
+        match read_member_signature(&format!("/dev/{}", member), *bytes) {
+            Some(sig) if !found.iter().any(|f| f.starts_with(&format!("/dev/{} ", member))) => {
+                found.push(format!("/dev/{} carries {}", member, sig));
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// "an mdadm RAID superblock" / "an LVM physical volume label", if present
+fn read_member_signature(path: &str, bytes: u64) -> Option<&'static str> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut f = fs::File::open(path).ok()?;
+    let mut at = |offset: u64, len: usize| -> Option<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        f.seek(SeekFrom::Start(offset)).ok()?;
+        f.read_exact(&mut buf).ok()?;
+        Some(buf)
+    };
+
+    if let Some(head) = at(0, 2048) {
+        if head.chunks(512).any(|s| s.starts_with(LVM_LABEL)) {
+            return Some("an LVM physical volume label");
+        }
+    }
+    // md 1.1 at the start, 1.2 at 4 KiB, 1.0 and 0.90 near the end
+    let mut offsets = vec![0, 4096];
+    if bytes >= 128 * 1024 {
+        offsets.push((bytes - 8192) & !4095);
+        offsets.push((bytes & !0xFFFF) - 0x10000);
+    }
+    offsets.into_iter()
+        .any(|off| at(off, 4).is_some_and(|m| m == MD_MAGIC))
+        .then_some("an mdadm RAID superblock")
+}
+
+// ─────────────────────────────────────────────
+//  IMAGE INSPECTION
+// ─────────────────────────────────────────────
+
+/// What the first sectors of an image look like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    /// ISO9660 with an MBR/GPT boot sector — boots when written raw to USB
+    HybridIso,
+    /// ISO9660 with an El Torito boot catalog only (optical-style boot)
+    ElToritoIso,
+    /// Raw disk image with an MBR boot signature or GPT header
+    DiskImage,
+    /// ISO9660 filesystem with no boot record at all
+    PlainIso,
+    /// Nothing recognizable
+    Unknown,
+}
+
+impl ImageKind {
+    pub fn has_boot_signature(&self) -> bool {
+        matches!(self, ImageKind::HybridIso | ImageKind::ElToritoIso | ImageKind::DiskImage)
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ImageKind::HybridIso => "hybrid ISO (USB bootable)",
+            ImageKind::ElToritoIso => "ISO with El Torito boot catalog",
+            ImageKind::DiskImage => "disk image (MBR/GPT)",
+            ImageKind::PlainIso => "ISO9660, no boot record",
+            ImageKind::Unknown => "unrecognized data",
+        }
+    }
+}
+
+/// ISO9660 volume descriptors start at sector 16 (2048-byte sectors)
+const ISO_VD_OFFSET: usize = 0x8000;
+/// Enough to cover the primary and boot-record volume descriptors
+const INSPECT_BYTES: usize = ISO_VD_OFFSET + 2 * 2048;
+
+/// Classify an image from its leading bytes
+pub fn classify_image(buf: &[u8]) -> ImageKind {
+    let at = |off: usize, sig: &[u8]| buf.get(off..off + sig.len()) == Some(sig);
+
+    let mbr = at(510, &[0x55, 0xAA]);
+    let gpt = at(512, b"EFI PART");
+    let iso9660 = at(ISO_VD_OFFSET + 1, b"CD001");
+    // Boot Record Volume Descriptor (type 0) right after the primary one
+    let el_torito = at(ISO_VD_OFFSET + 2048, &[0x00])
+        && at(ISO_VD_OFFSET + 2048 + 1, b"CD001")
+        && at(ISO_VD_OFFSET + 2048 + 7, b"EL TORITO SPECIFICATION");
+
+    match (iso9660, mbr || gpt, el_torito) {
+        (true, true, _) => ImageKind::HybridIso,
+        (true, false, true) => ImageKind::ElToritoIso,
+        (true, false, false) => ImageKind::PlainIso,
+        (false, true, _) => ImageKind::DiskImage,
+        (false, false, _) => ImageKind::Unknown,
+    }
+}
+
+/// Read the start of an image and look for ISO9660 / El Torito / MBR / GPT
+pub fn inspect_iso(path: &PathBuf) -> Result<ImageKind> {
+    use std::io::Read;
+    let mut buf = Vec::with_capacity(INSPECT_BYTES);
+
+    if let Some(tool) = compression_tool(path) {
+        // Peek at the decompressed stream, then stop the decompressor
+        let mut child = Command::new(tool)
+            .arg("-dc")
+            .arg(path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to launch {} — is it installed?", tool))?;
+        child.stdout.take().unwrap()
+            .take(INSPECT_BYTES as u64)
+            .read_to_end(&mut buf)
+            .with_context(|| format!("Cannot decompress {}", path.display()))?;
+        let _ = child.kill();
+        let _ = child.wait();
+    } else {
+        fs::File::open(path)
+            .with_context(|| format!("Cannot read image: {}", path.display()))?
+            .take(INSPECT_BYTES as u64)
+            .read_to_end(&mut buf)
+            .with_context(|| format!("Cannot read image: {}", path.display()))?;
+    }
+    Ok(classify_image(&buf))
+}
+
+/// Same detection as `inspect_iso`, applied to the first sectors of the device
+pub fn inspect_device(device: &UsbDevice) -> Result<ImageKind> {
+    use std::io::Read;
+    let mut buf = Vec::with_capacity(INSPECT_BYTES);
+    fs::File::open(&device.path)
+        .with_context(|| format!("Cannot read {}", device.path))?
+        .take(INSPECT_BYTES as u64)
+        .read_to_end(&mut buf)
+        .with_context(|| format!("Cannot read {}", device.path))?;
+    Ok(classify_image(&buf))
+}
+
+/// What the partition table on a device says about how it boots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionScheme {
+    Gpt { esp: bool },
+    /// `bootable` = a partition carries the active flag
+    Mbr { bootable: bool, esp: bool },
+    /// No MBR/GPT signature
+    Raw,
+}
+
+impl PartitionScheme {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            PartitionScheme::Gpt { esp: true } => "GPT with EFI System Partition",
+            PartitionScheme::Gpt { esp: false } => "GPT, no EFI System Partition",
+            PartitionScheme::Mbr { bootable: true, esp: true } => "MBR bootable, with EFI System Partition",
+            PartitionScheme::Mbr { bootable: true, esp: false } => "MBR bootable",
+            PartitionScheme::Mbr { bootable: false, esp: true } => "MBR with EFI System Partition",
+            PartitionScheme::Mbr { bootable: false, esp: false } => "MBR, no bootable partition",
+            PartitionScheme::Raw => "no partition table / raw",
+        }
+    }
+
+    /// Something a PC firmware would try to boot from
+    pub fn is_bootable(&self) -> bool {
+        match self {
+            PartitionScheme::Gpt { esp } => *esp,
+            PartitionScheme::Mbr { bootable, esp } => *bootable || *esp,
+            PartitionScheme::Raw => false,
+        }
+    }
+}
+
+/// MBR partition type of an EFI System Partition, and of a GPT's protective entry
+const MBR_TYPE_ESP: u8 = 0xEF;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+/// C12A7328-F81F-11D2-BA4B-00A0C93EC93B in on-disk (mixed-endian) order
+const GPT_ESP_GUID: [u8; 16] = [
+    0x28, 0x73, 0x2A, 0xC1, 0x1F, 0xF8, 0xD2, 0x11,
+    0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B,
+];
+/// Upper bound on the GPT entry array we read (128 entries × 128 bytes is the norm)
+const GPT_ENTRIES_MAX: u64 = 64 * 1024;
+
+/// Read the MBR and, if present, the GPT header and entry array of a device
+pub fn read_partition_scheme(device: &UsbDevice) -> Result<PartitionScheme> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = fs::File::open(&device.path)
+        .with_context(|| format!("Cannot read {}", device.path))?;
+    let sector = device.logical_sector_size.max(512);
+    let mut head = vec![0u8; (sector * 2) as usize];
+    file.read_exact(&mut head)
+        .with_context(|| format!("Cannot read {}", device.path))?;
+
+    // Same signatures classify_image looks for, at the device's sector size
+    let gpt = head.get(sector as usize..sector as usize + 8) == Some(b"EFI PART".as_slice());
+    let mbr = head[510..512] == [0x55, 0xAA];
+    if !gpt && !mbr {
+        return Ok(PartitionScheme::Raw);
+    }
+
+    // Four 16-byte MBR entries at 446: boot flag at +0, type at +4
+    let entries: Vec<&[u8]> = head[446..510].chunks(16).collect();
+    let protective = entries.iter().any(|e| e[4] == MBR_TYPE_GPT_PROTECTIVE);
+    if !gpt || !protective {
+        return Ok(PartitionScheme::Mbr {
+            bootable: entries.iter().any(|e| e[0] == 0x80 && e[4] != 0),
+            esp: entries.iter().any(|e| e[4] == MBR_TYPE_ESP),
+        });
+    }
+
+    let hdr = &head[sector as usize..];
+    let le32 = |b: &[u8]| u32::from_le_bytes(b.try_into().unwrap()) as u64;
+    let entries_lba = u64::from_le_bytes(hdr[72..80].try_into().unwrap());
+    let count = le32(&hdr[80..84]);
+    let entry_size = le32(&hdr[84..88]);
+    if entry_size < 16 {
+        return Ok(PartitionScheme::Gpt { esp: false });
+    }
+    let len = (count * entry_size).min(GPT_ENTRIES_MAX);
+    let mut table = Vec::with_capacity(len as usize);
+    file.seek(SeekFrom::Start(entries_lba.saturating_mul(sector)))
+        .and_then(|_| (&mut file).take(len).read_to_end(&mut table))
+        .with_context(|| format!("Cannot read the GPT on {}", device.path))?;
+
+    let esp = table
+        .chunks_exact(entry_size as usize)
+        .any(|e| e[..16] == GPT_ESP_GUID);
+    Ok(PartitionScheme::Gpt { esp })
+}
+
+/// On-disk size of the file
+pub fn iso_size(path: &PathBuf) -> Result<u64> {
+    Ok(fs::metadata(path)
+        .with_context(|| format!("Cannot read image: {}", path.display()))?
+        .len())
+}
+
+/// Bytes that end up on the device: the decompressed size for .gz/.xz/.zst
+/// when it can be determined, otherwise the file size
+pub fn image_size(path: &PathBuf) -> Result<u64> {
+    let file_bytes = iso_size(path)?;
+    if compression_tool(path).is_none() {
+        return Ok(file_bytes);
+    }
+    Ok(decompressed_size(path).unwrap_or(file_bytes))
+}
+
+// ─────────────────────────────────────────────
+//  SMART — best effort, via smartctl
+// ─────────────────────────────────────────────
+
+/// The few SMART values that say whether a stick can be trusted. Each is
+/// None when the drive (or its USB bridge) doesn't report it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmartInfo {
+    /// Overall self-assessment: true = PASSED/OK
+    pub healthy: Option<bool>,
+    pub power_on_hours: Option<u64>,
+    /// Remaining endurance in percent (100 = new)
+    pub life_left: Option<u8>,
+    pub reallocated_sectors: Option<u64>,
+}
+
+impl SmartInfo {
+    fn is_empty(&self) -> bool {
+        *self == SmartInfo::default()
+    }
+}
+
+/// `smartctl -a <device>`, parsed. None when smartctl is missing, can't open
+/// the device, or the bridge passes no SMART data through.
+pub fn read_smart(device: &UsbDevice) -> Option<SmartInfo> {
+    let out = Command::new("smartctl")
+        .args(["-a", &device.path])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    // Exit status is a bitmask; bits 0–1 mean the command line or the device open failed
+    if out.status.code().is_none_or(|c| c & 0b11 != 0) {
+        return None;
+    }
+    let smart = parse_smart(&String::from_utf8_lossy(&out.stdout));
+    (!smart.is_empty()).then_some(smart)
+}
+
+/// Parse the text report of `smartctl -a`: the ATA attribute table or the NVMe health log
+pub fn parse_smart(text: &str) -> SmartInfo {
+    let mut smart = SmartInfo::default();
+    // Leading digits of "1234", "1,234" or "1234h+05m+12.345s"
+    let number = |s: &str| -> Option<u64> {
+        let digits: String = s.chars().take_while(|c| c.is_ascii_digit() || *c == ',').filter(|c| *c != ',').collect();
+        digits.parse().ok()
+    };
+
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(result) = line.strip_prefix("SMART overall-health self-assessment test result:") {
+            smart.healthy = Some(result.trim() == "PASSED");
+        } else if let Some(result) = line.strip_prefix("SMART Health Status:") {
+            smart.healthy = Some(result.trim() == "OK");
+        } else if let Some(v) = line.strip_prefix("Power On Hours:") {
+            smart.power_on_hours = number(v.trim());
+        } else if let Some(v) = line.strip_prefix("Percentage Used:") {
+            smart.life_left = number(v.trim()).map(|used| 100u64.saturating_sub(used) as u8);
+        } else {
+            // ID# ATTRIBUTE_NAME FLAG VALUE WORST THRESH TYPE UPDATED WHEN_FAILED RAW_VALUE
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 10 || cols[0].parse::<u8>().is_err() {
+                continue;
+            }
+            match cols[1] {
+                "Power_On_Hours" => smart.power_on_hours = number(cols[9]),
+                // Normalized VALUE counts down from 100 as the flash wears
+                "Wear_Leveling_Count" | "Media_Wearout_Indicator" | "Percent_Lifetime_Remain" => {
+                    smart.life_left = number(cols[3]).map(|v| v.min(100) as u8)
+                }
+                "Reallocated_Sector_Ct" => smart.reallocated_sectors = number(cols[9]),
+                _ => {}
+            }
+        }
+    }
+    smart
+}
+
+// ─────────────────────────────────────────────
+//  STREAMED SOURCES — stdin and http(s)
+// ─────────────────────────────────────────────
+
+/// Image path meaning "read burn's standard input"
+pub const STDIN_IMAGE: &str = "-";
+
+pub fn is_stdin_image(path: &Path) -> bool {
+    path.as_os_str() == STDIN_IMAGE
+}
+
+pub fn is_url(path: &Path) -> bool {
+    let s = path.to_string_lossy();
+    s.starts_with("http://") || s.starts_with("https://")
+}
+
+/// Piped in or downloaded: read exactly once, front to back, and never
+/// inspected, checksummed or measured up front
+pub fn is_streamed(path: &Path) -> bool {
+    is_stdin_image(path) || is_url(path)
+}
+
+/// Last URL asked about → its Content-Length, so one run makes one HEAD request
+static STREAM_SIZE: Mutex<Option<(PathBuf, Option<u64>)>> = Mutex::new(None);
+
+/// Size of a streamed image if it is known before the first byte: the
+/// Content-Length of a URL (after redirects). Never known for stdin.
+pub fn stream_size(path: &Path) -> Option<u64> {
+    if !is_url(path) {
+        return None;
+    }
+    let mut memo = STREAM_SIZE.lock().ok()?;
+    match memo.as_ref() {
+        Some((p, size)) if p == path => *size,
+        _ => {
+            let size = content_length(path);
+            log::info!("{}: Content-Length {:?}", path.display(), size);
+            *memo = Some((path.to_path_buf(), size));
+            size
+        }
+    }
+}
+
+/// HEAD request through curl, following redirects
+fn content_length(url: &Path) -> Option<u64> {
+    let out = Command::new("curl")
+        .args(["-fsIL", "--proto", "=http,https"])
+        .arg(url)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    // One header block per redirect — only the last one describes the file
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .rev()
+        .take_while(|l| !l.starts_with("HTTP/"))
+        .find_map(|l| {
+            let (name, value) = l.split_once(':')?;
+            name.trim().eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse().ok())?
+        })
+}
+
+// ─────────────────────────────────────────────
+//  COMPRESSED IMAGES
+// ─────────────────────────────────────────────
+
+/// The logical bytes of an image — what ends up on the device. Compressed
+/// images are read from the decompressor's stdout, which is stopped on drop.
+pub struct ImageReader {
+    inner: Box<dyn std::io::Read + Send>,
+    unpack: Option<std::process::Child>,
+}
+
+impl std::io::Read for ImageReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Drop for ImageReader {
+    fn drop(&mut self) {
+        // Stopping before the end of the stream is expected, so the exit status is ignored
+        if let Some(u) = self.unpack.as_mut() {
+            let _ = u.kill();
+            let _ = u.wait();
+        }
+    }
+}
+
+/// Open an image for reading its logical bytes, decompressing .gz/.xz/.zst on the fly
+pub fn open_image(path: &Path) -> Result<ImageReader> {
+    match compression_tool(path) {
+        Some(tool) => {
+            let mut unpack = Command::new(tool)
+                .arg("-dc")
+                .arg(path)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .with_context(|| format!("{} not found", tool))?;
+            let stdout = unpack.stdout.take().unwrap();
+            Ok(ImageReader { inner: Box::new(stdout), unpack: Some(unpack) })
+        }
+        None => {
+            let f = fs::File::open(path)
+                .with_context(|| format!("Cannot read image: {}", path.display()))?;
+            Ok(ImageReader { inner: Box::new(f), unpack: None })
+        }
+    }
+}
+
+/// Exact logical length of an image. Compressed images whose metadata doesn't
+/// record it are decompressed once and counted.
+pub fn logical_size(path: &PathBuf) -> Result<u64> {
+    if compression_tool(path).is_none() {
+        return iso_size(path);
+    }
+    if let Some(n) = decompressed_size(path) {
+        return Ok(n);
+    }
+    log::info!("{}: size not in the metadata, decompressing to count it", path.display());
+    std::io::copy(&mut open_image(path)?, &mut std::io::sink())
+        .with_context(|| format!("Cannot decompress {}", path.display()))
+}
+
+/// (De)compressor for an image path, from its extension.
+/// Streamed sources are written as they arrive.
+pub fn compression_tool(path: &Path) -> Option<&'static str> {
+    if is_streamed(path) {
+        return None;
+    }
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => Some("gzip"),
+        Some("xz") => Some("xz"),
+        Some("zst") | Some("zstd") => Some("zstd"),
+        _ => None,
+    }
+}
+
+/// Estimate the decompressed size of a .gz / .xz / .zst image from its metadata
+pub fn decompressed_size(path: &PathBuf) -> Option<u64> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut f = fs::File::open(path).ok()?;
+    let len = f.metadata().ok()?.len();
+
+    match compression_tool(path)? {
+        "gzip" => {
+            // ISIZE trailer: uncompressed size mod 2^32
+            if len < 18 {
+                return None;
+            }
+            let mut magic = [0u8; 2];
+            f.read_exact(&mut magic).ok()?;
+            if magic != [0x1f, 0x8b] {
+                return None;
+            }
+            let mut trailer = [0u8; 4];
+            f.seek(SeekFrom::End(-4)).ok()?;
+            f.read_exact(&mut trailer).ok()?;
+            Some(gzip_size_from_isize(u32::from_le_bytes(trailer), len))
+        }
+        "xz" => {
+            // Only the tail is needed: stream footer + index
+            let tail_len = len.min(1024 * 1024);
+            let mut tail = vec![0u8; tail_len as usize];
+            f.seek(SeekFrom::End(-(tail_len as i64))).ok()?;
+            f.read_exact(&mut tail).ok()?;
+            xz_uncompressed_size(&tail)
+        }
+        "zstd" => {
+            let mut head = [0u8; 18];
+            let n = f.read(&mut head).ok()?;
+            zstd_content_size(&head[..n])
+        }
+        _ => None,
+    }
+}
+
+/// gzip only stores the size mod 2^32; pick the smallest candidate that is
+/// plausible for the compressed length (deflate can grow data only slightly)
+fn gzip_size_from_isize(isize: u32, compressed_len: u64) -> u64 {
+    let slack = compressed_len / 1000 + 1024;
+    let mut size = isize as u64;
+    while size + slack < compressed_len {
+        size += 1 << 32;
+    }
+    size
+}
+
+/// Decode an xz multibyte integer → (value, bytes used)
+fn xz_varint(buf: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, b) in buf.iter().take(9).enumerate() {
+        value |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Sum the uncompressed sizes in the index of the last xz stream
+fn xz_uncompressed_size(tail: &[u8]) -> Option<u64> {
+    // Strip stream padding (multiples of four zero bytes)
+    let mut end = tail.len();
+    while end >= 4 && tail[end - 4..end] == [0, 0, 0, 0] {
+        end -= 4;
+    }
+    if end < 12 || &tail[end - 2..end] != b"YZ" {
+        return None;
+    }
+    let footer = &tail[end - 12..end];
+    let backward = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]) as usize;
+    let index_size = (backward + 1) * 4;
+    let index_start = (end - 12).checked_sub(index_size)?;
+    let index = &tail[index_start..end - 12];
+
+    if index.first() != Some(&0x00) {
+        return None;
+    }
+    let mut pos = 1;
+    let (records, used) = xz_varint(&index[pos..])?;
+    pos += used;
+
+    let mut total = 0u64;
+    for _ in 0..records {
+        let (_unpadded, used) = xz_varint(index.get(pos..)?)?;
+        pos += used;
+        let (uncompressed, used) = xz_varint(index.get(pos..)?)?;
+        pos += used;
+        total = total.checked_add(uncompressed)?;
+    }
+    Some(total)
+}
+
+/// Frame_Content_Size from a zstd frame header, if the encoder stored it
+fn zstd_content_size(head: &[u8]) -> Option<u64> {
+    if head.get(..4)? != [0x28, 0xb5, 0x2f, 0xfd] {
+        return None;
+    }
+    let fhd = *head.get(4)?;
+    let fcs_flag = fhd >> 6;
+    let single_segment = fhd & 0x20 != 0;
+    let dict_id_len = [0, 1, 2, 4][(fhd & 0x03) as usize];
+
+    let pos = 5 + if single_segment { 0 } else { 1 } + dict_id_len;
+    let fcs_len = match (fcs_flag, single_segment) {
+        (0, true) => 1,
+        (0, false) => return None,
+        (1, _) => 2,
+        (2, _) => 4,
+        _ => 8,
+    };
+    let field = head.get(pos..pos + fcs_len)?;
+
+    let mut bytes = [0u8; 8];
+    bytes[..fcs_len].copy_from_slice(field);
+    let value = u64::from_le_bytes(bytes);
+    Some(if fcs_len == 2 { value + 256 } else { value })
+}
+
+// ─────────────────────────────────────────────
+//  SOURCE CHECKSUM
+// ─────────────────────────────────────────────
+
+/// Algorithm of a published image checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashKind {
+    Md5,
+    Sha256,
+}
+
+impl HashKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            HashKind::Md5 => "MD5",
+            HashKind::Sha256 => "SHA-256",
+        }
+    }
+
+    fn hex_len(&self) -> usize {
+        match self {
+            HashKind::Md5 => 32,
+            HashKind::Sha256 => 64,
+        }
+    }
+}
+
+/// A checksum for the image found next to it
+#[derive(Debug, Clone)]
+pub struct SourceChecksum {
+    pub kind: HashKind,
+    /// Lowercase hex
+    pub expected: String,
+    /// The file it came from
+    pub file: PathBuf,
+}
+
+/// Look for `<image>.sha256`, `<image>.md5` or a `SHA256SUMS` entry, in that order
+pub fn find_source_checksum(image: &Path) -> Option<SourceChecksum> {
+    let name = image.file_name()?.to_string_lossy().to_string();
+    let dir = image.parent().unwrap_or(Path::new("."));
+    let candidates = [
+        (dir.join(format!("{}.sha256", name)), HashKind::Sha256, false),
+        (dir.join(format!("{}.md5", name)), HashKind::Md5, false),
+        (dir.join("SHA256SUMS"), HashKind::Sha256, true),
+    ];
+    candidates.into_iter().find_map(|(file, kind, need_name)| {
+        let text = fs::read_to_string(&file).ok()?;
+        let expected = checksum_entry(&text, &name, kind, need_name)?;
+        log::info!("{} checksum for {} from {}", kind.describe(), name, file.display());
+        Some(SourceChecksum { kind, expected, file })
+    })
+}
+
+/// The hash for `name` in checksum-file text ("<hex>  <name>" / "<hex> *<name>" lines).
+/// A per-image file may hold a bare hash or a differently named entry;
+/// a shared list (`need_name`) must name the image.
+fn checksum_entry(text: &str, name: &str, kind: HashKind, need_name: bool) -> Option<String> {
+    let entries: Vec<(String, Option<&str>)> = text.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let hash = parts.next()?;
+            if hash.len() != kind.hex_len() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let file = parts.next()
+                .map(|f| f.trim_start_matches('*').trim_start_matches("./"));
+            Some((hash.to_ascii_lowercase(), file))
+        })
+        .collect();
+
+    let named = entries.iter().find(|(_, f)| *f == Some(name));
+    match named {
+        Some((hash, _)) => Some(hash.clone()),
+        None if need_name => None,
+        None => entries.into_iter().next().map(|(hash, _)| hash),
+    }
+}
+
+/// Incremental hasher for either algorithm
+enum Hasher {
+    Md5(md5::Context),
+    Sha256(sha2::Sha256),
+}
+
+impl Hasher {
+    fn new(kind: HashKind) -> Self {
+        match kind {
+            HashKind::Md5 => Hasher::Md5(md5::Context::new()),
+            HashKind::Sha256 => Hasher::Sha256(<sha2::Sha256 as sha2::Digest>::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(ctx) => ctx.consume(data),
+            Hasher::Sha256(h) => sha2::Digest::update(h, data),
+        }
+    }
+
+    /// Lowercase hex
+    fn finish(self) -> String {
+        match self {
+            Hasher::Md5(ctx) => format!("{:x}", ctx.compute()),
+            Hasher::Sha256(h) => format!("{:x}", sha2::Digest::finalize(h)),
+        }
+    }
+}
+
+/// Region size for `--checkpoint` hashes
+pub const CHECKPOINT_CHUNK: u64 = 256 * 1024 * 1024;
+
+/// Hash of a whole read, plus one per `chunk` bytes when asked for —
+/// comparing those localizes a mismatch to a region
+#[derive(Debug, Clone, Default)]
+pub struct ReadHashes {
+    pub hash: String,
+    /// One per chunk, in order; the last one may cover less
+    pub chunks: Vec<String>,
+}
+
+/// [`Hasher`] over the whole stream and, optionally, per fixed-size chunk
+struct ChunkedHasher {
+    kind: HashKind,
+    whole: Hasher,
+    chunk: Option<u64>,
+    current: Hasher,
+    filled: u64,
+    chunks: Vec<String>,
+}
+
+impl ChunkedHasher {
+    fn new(kind: HashKind, chunk: Option<u64>) -> Self {
+        ChunkedHasher {
+            kind,
+            whole: Hasher::new(kind),
+            chunk: chunk.filter(|c| *c > 0),
+            current: Hasher::new(kind),
+            filled: 0,
+            chunks: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.whole.update(data);
+        let Some(chunk) = self.chunk else { return };
+        while !data.is_empty() {
+            let take = ((chunk - self.filled) as usize).min(data.len());
+            self.current.update(&data[..take]);
+            self.filled += take as u64;
+            data = &data[take..];
+            if self.filled == chunk {
+                let full = std::mem::replace(&mut self.current, Hasher::new(self.kind));
+                self.chunks.push(full.finish());
+                self.filled = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> ReadHashes {
+        if self.filled > 0 {
+            self.chunks.push(self.current.finish());
+        }
+        ReadHashes { hash: self.whole.finish(), chunks: self.chunks }
+    }
+}
+
+/// Hash up to `limit` bytes of `reader` → (hashes, bytes hashed); short if it ends first
+fn hash_reader(
+    reader: &mut dyn std::io::Read,
+    kind: HashKind,
+    limit: u64,
+    chunk: Option<u64>,
+    progress: &dyn Progress,
+) -> Result<(ReadHashes, u64)> {
+    let mut hasher = ChunkedHasher::new(kind, chunk);
+    let mut buf = vec![0u8; DEFAULT_BLOCK_SIZE as usize];
+    let mut done = 0u64;
+    progress.start(limit);
+    while done < limit {
+        let want = (limit - done).min(buf.len() as u64) as usize;
+        let n = match reader.read(&mut buf[..want]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).with_context(|| format!("read error at byte {}", done)),
+        };
+        hasher.update(&buf[..n]);
+        done += n as u64;
+        progress.bytes(done);
+    }
+    Ok((hasher.finish(), done))
+}
+
+/// Hash a whole file in-process → lowercase hex
+pub fn hash_file(path: &Path, kind: HashKind, progress: &dyn Progress) -> Result<String> {
+    let mut f = fs::File::open(path)
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    let len = f.metadata()
+        .with_context(|| format!("Cannot read {}", path.display()))?
+        .len();
+    let (hashes, _) = hash_reader(&mut f, kind, len, None, progress)
+        .with_context(|| format!("Cannot hash {}", path.display()))?;
+    Ok(hashes.hash)
+}
+
+/// Read a whole file once, front to back, so a bad sector on the source
+/// drive shows up before the target is touched → bytes read
+pub fn read_through(path: &Path, progress: &dyn Progress) -> Result<u64> {
+    use std::io::Read;
+    let mut f = fs::File::open(path)
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    let len = f.metadata()
+        .with_context(|| format!("Cannot read {}", path.display()))?
+        .len();
+    let mut buf = vec![0u8; DEFAULT_BLOCK_SIZE as usize];
+    let mut done = 0u64;
+    progress.start(len);
+    loop {
+        let n = match f.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                return Err(e).with_context(|| format!("{}: read error at byte {}", path.display(), done))
+            }
+        };
+        done += n as u64;
+        progress.bytes(done);
+    }
+    if done < len {
+        return Err(burn_err!("{} ended after {} of {} bytes", path.display(), done, len));
+    }
+    Ok(done)
+}
+
+// ─────────────────────────────────────────────
+//  DD
+// ─────────────────────────────────────────────
+
+/// dd bs= when the caller doesn't pick one
+pub const DEFAULT_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// fdatasync the device this often while dd runs without a synchronous oflag.
+/// Small enough that a flash stick's page cache never hides more than a few seconds of writing.
+pub const DEFAULT_SYNC_INTERVAL: u64 = 64 * 1024 * 1024;
+
+/// Single-quote an argument for a printed, copy-pasteable command line
+/// when it holds anything beyond plain path characters (spaces, quotes, `$`…)
+pub fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=,@+".contains(c)) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Format bytes as a dd-compatible size: 4194304 → "4M"
+pub fn format_block_size(bytes: u64) -> String {
+    const G: u64 = 1024 * 1024 * 1024;
+    const M: u64 = 1024 * 1024;
+    const K: u64 = 1024;
+    if bytes >= G && bytes.is_multiple_of(G) {
+        format!("{}G", bytes / G)
+    } else if bytes >= M && bytes.is_multiple_of(M) {
+        format!("{}M", bytes / M)
+    } else if bytes >= K && bytes.is_multiple_of(K) {
+        format!("{}K", bytes / K)
+    } else {
+        bytes.to_string()
+    }
+}
+
+/// `--dd-path` override, set once at startup
+static DD_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use this dd instead of the one on PATH; only the first call counts
+pub fn set_dd_path(path: PathBuf) {
+    let _ = DD_PATH.set(path);
+}
+
+/// The dd override, if one was set
+pub fn dd_path_override() -> Option<&'static Path> {
+    DD_PATH.get().map(PathBuf::as_path)
+}
+
+pub fn dd_bin() -> PathBuf {
+    DD_PATH.get().cloned().unwrap_or_else(|| PathBuf::from("dd"))
+}
+
+/// Parse "1234567168 bytes (1.2 GB...) copied" → 1234567168
+///
+/// Locales may group digits: "1,234,567,168 bytes", "1 234 567 168 bytes",
+/// "1.234.567.168 bytes" — everything before the literal "bytes" is the count.
+pub fn parse_dd_bytes(line: &str) -> Option<u64> {
+    let head = &line[..line.find("bytes")?];
+    let digits: String = head
+        .trim_end()
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit() || matches!(c, ',' | '.' | '\'' | ' ' | '\u{a0}' | '\u{202f}'))
+        .filter(|c| c.is_ascii_digit())
+        .collect();
+    digits.chars().rev().collect::<String>().parse().ok()
+}
+
+/// Follow dd's `status=progress` output on stderr and report bytes copied.
+/// The thread returns dd's own "dd: …" lines (errors), if it printed any.
+///
+/// dd with status=progress writes to stderr lines like:
+/// "1234567168 bytes (1.2 GB, 1.1 GiB) copied, 5.1 s, 242 MB/s"
+pub fn spawn_dd_progress<F>(
+    stderr: ChildStderr,
+    running: Arc<AtomicBool>,
+    mut on_bytes: F,
+) -> thread::JoinHandle<Vec<String>>
+where
+    F: FnMut(u64) + Send + 'static,
+{
+    thread::spawn(move || {
+        // dd status=progress uses \r not \n — read byte by byte
+        use std::io::Read;
+        let mut reader = BufReader::new(stderr);
+        let mut line = String::new();
+        let mut messages = Vec::new();
+
+        loop {
+            if !running.load(Ordering::SeqCst) { break; }
+            let mut byte = [0u8; 1];
+            match reader.read(&mut byte) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let ch = byte[0] as char;
+                    if ch == '\r' || ch == '\n' {
+                        let trimmed = line.trim();
+                        if trimmed.contains("bytes") && trimmed.contains("copied") {
+                            if let Some(b) = parse_dd_bytes(trimmed) {
+                                on_bytes(b);
+                            }
+                        } else if trimmed.starts_with("dd:") {
+                            messages.push(trimmed.to_string());
+                        }
+                        line.clear();
+                    } else {
+                        line.push(ch);
+                    }
+                }
+            }
+        }
+        messages
+    })
+}
+
+/// dd ran and exited non-zero — attached as the cause of write_iso's error
+/// so callers can tell a failing stick from a failure to start or a stall
+#[derive(Debug)]
+pub struct DdError {
+    pub code: Option<i32>,
+    /// dd's own "dd: …" lines
+    pub messages: Vec<String>,
+}
+
+impl DdError {
+    /// EIO from the device — the kind a flaky stick or controller produces,
+    /// as opposed to a missing device, a full one or a permission problem
+    pub fn is_io_error(&self) -> bool {
+        self.messages.iter().any(|m| m.contains("Input/output error"))
+    }
+
+    /// EINVAL — what opening or writing with oflag=direct gives where the
+    /// device or filesystem doesn't support O_DIRECT
+    pub fn is_invalid_argument(&self) -> bool {
+        self.messages.iter().any(|m| m.contains("Invalid argument"))
+    }
+}
+
+impl std::fmt::Display for DdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.messages.is_empty() {
+            true => write!(f, "dd exited with code {}", self.code.unwrap_or(-1)),
+            false => write!(f, "{}", self.messages.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for DdError {}
+
+/// posix_fadvise(POSIX_FADV_DONTNEED) on a range that has just been flushed,
+/// so a long write without oflag=sync doesn't balloon the page cache
+fn drop_cached(f: &fs::File, offset: u64, len: u64) {
+    use std::os::fd::AsRawFd;
+    extern "C" {
+        fn posix_fadvise(fd: i32, offset: i64, len: i64, advice: i32) -> i32;
+    }
+    const POSIX_FADV_DONTNEED: i32 = 4;
+    // SAFETY: advisory call on an open fd we own; no memory is handed over
+    let rc = unsafe { posix_fadvise(f.as_raw_fd(), offset as i64, len as i64, POSIX_FADV_DONTNEED) };
+    if rc != 0 {
+        log::debug!("posix_fadvise(DONTNEED) at {}+{} failed: {}", offset, len, rc);
+    }
+}
+
+/// Bytes a process has written so far, from /proc/<pid>/io — locale-independent,
+/// unlike dd's stderr. `write_bytes` (what reached the block layer), or `wchar`
+/// while nothing is accounted there (e.g. a target on tmpfs).
+pub fn poll_proc_io(pid: u32) -> Option<u64> {
+    let io = fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+    let field = |name: &str| {
+        io.lines().find_map(|l| l.strip_prefix(name)?.trim().parse::<u64>().ok())
+    };
+    match field("write_bytes:")? {
+        0 => field("wchar:"),
+        n => Some(n),
+    }
+}
+
+/// Poll the child's /proc/<pid>/io until `done`; stand-in for
+/// [`spawn_dd_progress`] when dd runs with status=none
+fn spawn_proc_io_progress<F>(
+    pid: u32,
+    running: Arc<AtomicBool>,
+    done: Arc<AtomicBool>,
+    mut on_bytes: F,
+) -> thread::JoinHandle<()>
+where
+    F: FnMut(u64) + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut last = 0;
+        while running.load(Ordering::SeqCst) && !done.load(Ordering::SeqCst) {
+            match poll_proc_io(pid) {
+                Some(b) if b > last => {
+                    last = b;
+                    on_bytes(b);
+                }
+                Some(_) => {}
+                // Exited (or reaped) between polls
+                None => break,
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    })
+}
+
+/// Stop the tracked dd if `position` doesn't move for `timeout`.
+/// Returns true if it fired.
+fn spawn_stall_watchdog(
+    position: Arc<AtomicU64>,
+    timeout: Duration,
+    done: Arc<AtomicBool>,
+) -> thread::JoinHandle<bool> {
+    thread::spawn(move || {
+        let mut last = position.load(Ordering::SeqCst);
+        let mut since = Instant::now();
+        while !done.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(250));
+            let pos = position.load(Ordering::SeqCst);
+            if pos != last {
+                last = pos;
+                since = Instant::now();
+            } else if since.elapsed() >= timeout {
+                log::error!("Write stalled at {} bytes for {:?}, stopping dd", pos, timeout);
+                stop_active_child();
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// ─────────────────────────────────────────────
+//  WRITE
+// ─────────────────────────────────────────────
+
+/// Partial write: `len` bytes at the same `offset` in the image and on the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteRange {
+    pub offset: u64,
+    pub len: u64,
+}
+
+impl WriteRange {
+    /// Resolve `--offset` / `--size` against the image; None = whole image
+    pub fn resolve(offset: Option<u64>, size: Option<u64>, iso_bytes: u64) -> Result<Option<WriteRange>> {
+        if offset.is_none() && size.is_none() {
+            return Ok(None);
+        }
+        let offset = offset.unwrap_or(0);
+        if offset >= iso_bytes {
+            return Err(burn_err!(
+                "--offset {} is past the end of the image ({} bytes)",
+                offset, iso_bytes
+            ));
+        }
+        let len = size.unwrap_or(iso_bytes - offset);
+        if len == 0 {
+            return Err(burn_err!("--size must be greater than zero"));
+        }
+        if offset + len > iso_bytes {
+            return Err(burn_err!(
+                "--offset {} + --size {} runs past the end of the image ({} bytes)",
+                offset, len, iso_bytes
+            ));
+        }
+        Ok(Some(WriteRange { offset, len }))
+    }
+
+    pub fn end(&self) -> u64 {
+        self.offset + self.len
+    }
+
+    /// dd operands for the range: whole blocks when aligned to `bs`, byte units otherwise
+    pub fn dd_args(&self, bs: u64) -> Vec<String> {
+        let mut args = if self.offset.is_multiple_of(bs) && self.len.is_multiple_of(bs) {
+            vec![
+                format!("skip={}", self.offset / bs),
+                format!("seek={}", self.offset / bs),
+                format!("count={}", self.len / bs),
+            ]
+        } else {
+            vec![
+                format!("skip={}", self.offset),
+                format!("seek={}", self.offset),
+                format!("count={}", self.len),
+                "iflag=skip_bytes,count_bytes".into(),
+                "oflag=seek_bytes".into(),
+            ]
+        };
+        // Keep the rest of a target file intact
+        args.push("conv=notrunc".into());
+        args
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "bytes {}–{} ({})",
+            self.offset,
+            self.end(),
+            format_size(self.len)
+        )
+    }
+}
+
+/// One write for [`write_iso`]; start from [`WriteOptions::new`]
+#[derive(Clone)]
+pub struct WriteOptions {
+    /// Source image (.iso/.img, or .gz/.xz/.zst decompressed on the fly)
+    pub image: PathBuf,
+    pub device: UsbDevice,
+    pub block_size: u64,
+    /// Only write part of the image
+    pub range: Option<WriteRange>,
+    /// dd conv= / oflag= values (None = not passed)
+    pub dd_conv: Option<String>,
+    pub dd_oflag: Option<String>,
+    /// Hash the image on its way into dd → `WriteOutcome::source_md5`
+    pub hash_source: bool,
+    /// Stop dd when the write stalls this long
+    pub timeout: Option<Duration>,
+    /// fdatasync the device every this many bytes (None = only dd's own flags)
+    pub sync_interval: Option<u64>,
+    /// Run dd with status=none and follow /proc/<pid>/io instead of its stderr.
+    /// Falls back to status=progress where /proc/<pid>/io can't be read.
+    pub dd_status_none: bool,
+    /// Cap dd's input at this many bytes/s with `pv -L` (pv must be installed)
+    pub rate_limit: Option<u64>,
+    /// Read up to this many bytes of the image ahead of dd on a thread of our
+    /// own (0 = dd reads the source itself). Not for partial writes.
+    pub read_ahead: u64,
+    /// Clear to stop early (e.g. from a Ctrl-C handler)
+    pub running: Arc<AtomicBool>,
+    pub progress: Arc<dyn Progress>,
+}
+
+impl WriteOptions {
+    /// Whole image, 4M blocks, oflag=sync, no progress reporting
+    pub fn new(image: PathBuf, device: UsbDevice) -> Self {
+        WriteOptions {
+            image,
+            device,
+            block_size: DEFAULT_BLOCK_SIZE,
+            range: None,
+            dd_conv: None,
+            dd_oflag: Some("sync".to_string()),
+            hash_source: false,
+            timeout: None,
+            sync_interval: Some(DEFAULT_SYNC_INTERVAL),
+            dd_status_none: false,
+            rate_limit: None,
+            read_ahead: 0,
+            running: Arc::new(AtomicBool::new(true)),
+            progress: Arc::new(NoProgress),
+        }
+    }
+
+    /// Whether the image goes through [`ReadAhead`]; a partial write keeps
+    /// `if=` so dd can seek to the offset instead of reading up to it
+    fn reads_ahead(&self) -> bool {
+        self.read_ahead > 0 && self.range.is_none()
+    }
+
+    /// status=none was asked for and /proc/<pid>/io is readable here
+    fn polls_proc_io(&self) -> bool {
+        self.dd_status_none && poll_proc_io(std::process::id()).is_some()
+    }
+
+    /// dd operands; the input comes from a pipe for compressed or hashed sources
+    fn dd_args(&self) -> Vec<String> {
+        let mut args = vec![
+            format!("of={}", self.device.path),
+            format!("bs={}", format_block_size(self.block_size)),
+            format!("status={}", if self.polls_proc_io() { "none" } else { "progress" }),
+        ];
+        if let Some(oflag) = &self.dd_oflag {
+            args.push(format!("oflag={}", oflag));
+        }
+        if let Some(conv) = &self.dd_conv {
+            args.push(format!("conv={}", conv));
+        }
+        if compression_tool(&self.image).is_some()
+            || self.hash_source
+            || is_streamed(&self.image)
+            || self.rate_limit.is_some()
+            || self.reads_ahead()
+        {
+            // Pipes return short reads; keep the blocks full
+            args.push("iflag=fullblock".into());
+        } else {
+            args.insert(0, format!("if={}", self.image.display()));
+        }
+        if let Some(range) = self.range {
+            args.extend(range.dd_args(self.block_size));
+        }
+        args
+    }
+
+    /// Effective periodic flush: none when oflag=sync/dsync/direct already
+    /// waits for the media on every block
+    pub fn periodic_sync(&self) -> Option<u64> {
+        let synchronous = self.dd_oflag.as_deref().is_some_and(|flags| {
+            flags.split(',').any(|f| matches!(f, "sync" | "dsync" | "direct"))
+        });
+        self.sync_interval.filter(|n| *n > 0 && !synchronous)
+    }
+
+    /// The write as a shell pipeline, for dry-runs and logs
+    pub fn command_line(&self) -> String {
+        let args = self.dd_args().iter().map(|a| shell_quote(a)).collect::<Vec<_>>().join(" ");
+        let image = shell_quote(&self.image.to_string_lossy());
+        let pace = self.rate_limit.map(|r| format!("pv -q -L {}", r));
+        let source = match (compression_tool(&self.image), &pace) {
+            (_, Some(pv)) if is_stdin_image(&self.image) => pv.clone(),
+            _ if is_stdin_image(&self.image) => "cat".to_string(),
+            _ if is_url(&self.image) => format!("curl -fsSL {}", image),
+            (Some(tool), _) => format!("{} -dc {}", tool, image),
+            (None, Some(pv)) => format!("{} {}", pv, image),
+            (None, None) => format!("cat {}", image),
+        };
+        let unpacked = compression_tool(&self.image).is_some() || is_url(&self.image);
+        let source = match &pace {
+            Some(pv) if unpacked => format!("{} | {}", source, pv),
+            _ => source,
+        };
+        // burn's own read-ahead shows as the plain `cat` it stands in for
+        let piped = unpacked || pace.is_some() || self.reads_ahead();
+        // The streamed hash is taken in-process, on the same pipe
+        match piped || self.hash_source {
+            true => format!("{} | dd {}", source, args),
+            false => format!("dd {}", args),
+        }
+    }
+}
+
+/// What [`write_iso`] did
+#[derive(Debug, Clone, Default)]
+pub struct WriteOutcome {
+    pub bytes_written: u64,
+    /// MD5 of the whole image, when `hash_source` was set and the hash covered it
+    pub source_md5: Option<String>,
+    pub duration: Duration,
+}
+
+/// Bytes zeroed at each end of the device by [`zap_device`]
+pub const ZAP_BYTES: u64 = 1024 * 1024;
+
+/// Zero the first and last [`ZAP_BYTES`] of the device so no stale MBR, GPT
+/// header or — the usual culprit — backup GPT at the end survives a reflash.
+/// "The end" is where the kernel says it is, never an `--assume-device-size`.
+pub fn zap_device(device: &UsbDevice) -> Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+    revalidate_device(&RealSysfs, device)?;
+    let size = device.reported_size;
+    let len = ZAP_BYTES.min(size);
+    let zeros = vec![0u8; len as usize];
+    let mut f = fs::OpenOptions::new()
+        .write(true)
+        .open(&device.path)
+        .with_context(|| format!("Cannot open {} to clear its partition tables", device.path))?;
+    for offset in [0, size - len] {
+        f.seek(SeekFrom::Start(offset))
+            .and_then(|_| f.write_all(&zeros))
+            .with_context(|| format!("Cannot zero {} at byte {}", device.path, offset))?;
+    }
+    f.sync_all().with_context(|| format!("Cannot flush {}", device.path))?;
+    log::info!("Zeroed the first and last {} bytes of {}", len, device.path);
+    Ok(())
+}
+
+/// The kernel accepts discard (TRIM) for this device: queue/discard_max_bytes > 0
+pub fn supports_discard(device: &UsbDevice) -> bool {
+    sysfs_read(&format!("/sys/block/{}/queue/discard_max_bytes", device.name))
+        .and_then(|s| s.parse::<u64>().ok())
+        .is_some_and(|n| n > 0)
+}
+
+/// Discard every block with `blkdiscard`, so the controller starts the write
+/// from erased flash instead of garbage-collecting old data as it goes
+pub fn discard_device(device: &UsbDevice) -> Result<()> {
+    revalidate_device(&RealSysfs, device)?;
+    let out = Command::new("blkdiscard")
+        .arg(&device.path)
+        .output()
+        .context("Failed to run blkdiscard")?;
+    if !out.status.success() {
+        return Err(burn_err!(
+            "blkdiscard {} failed: {}",
+            device.path,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    log::info!("Discarded all blocks of {}", device.path);
+    Ok(())
+}
+
+/// Stall on the source this long before it's reported as the bottleneck
+const SOURCE_WAIT_REPORT: Duration = Duration::from_secs(1);
+
+/// The image, read on its own thread into a bounded ring of buffers. A slow
+/// source (a spinning disk, a network mount) and the device then don't wait
+/// on each other block by block, and a stall can be pinned on the source.
+struct ReadAhead {
+    rx: crossbeam_channel::Receiver<std::io::Result<Vec<u8>>>,
+    current: Vec<u8>,
+    pos: usize,
+    progress: Arc<dyn Progress>,
+}
+
+impl ReadAhead {
+    /// Up to `budget` bytes in flight, in buffers of `chunk`
+    fn spawn(
+        mut source: Box<dyn std::io::Read + Send>,
+        chunk: usize,
+        budget: u64,
+        progress: Arc<dyn Progress>,
+    ) -> Self {
+        let depth = (budget / chunk as u64).max(2) as usize;
+        let (tx, rx) = crossbeam_channel::bounded(depth);
+        thread::spawn(move || loop {
+            let mut buf = vec![0u8; chunk];
+            let mut filled = 0;
+            // Pipes return short reads — fill the buffer unless the source ends
+            while filled < chunk {
+                match source.read(&mut buf[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        let _ = tx.send(Err(e));
+                        return;
+                    }
+                }
+            }
+            if filled == 0 {
+                return;
+            }
+            buf.truncate(filled);
+            if tx.send(Ok(buf)).is_err() {
+                return; // the writer is gone
+            }
+        });
+        ReadAhead { rx, current: Vec::new(), pos: 0, progress }
+    }
+}
+
+impl std::io::Read for ReadAhead {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        use crossbeam_channel::RecvTimeoutError;
+        if self.pos == self.current.len() {
+            let next = match self.rx.recv_timeout(SOURCE_WAIT_REPORT) {
+                Ok(block) => Some(block),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.progress.waiting_on_source(true);
+                    let block = self.rx.recv().ok();
+                    self.progress.waiting_on_source(false);
+                    block
+                }
+                Err(RecvTimeoutError::Disconnected) => None,
+            };
+            match next {
+                None => return Ok(0),
+                Some(block) => {
+                    self.current = block?;
+                    self.pos = 0;
+                }
+            }
+        }
+        let n = out.len().min(self.current.len() - self.pos);
+        out[..n].copy_from_slice(&self.current[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Copy `source` into dd's stdin on its own thread → bytes copied. Only a
+/// failing read is an error here; dd closing its end is dd's to report.
+fn spawn_feed(
+    mut source: Box<dyn std::io::Read + Send>,
+    mut sink: std::process::ChildStdin,
+) -> thread::JoinHandle<Result<u64>> {
+    use std::io::{Read, Write};
+    thread::spawn(move || {
+        let mut buf = vec![0u8; 1 << 20];
+        let mut total = 0u64;
+        loop {
+            let n = source.read(&mut buf).context("Cannot read image")?;
+            if n == 0 || sink.write_all(&buf[..n]).is_err() {
+                break;
+            }
+            total += n as u64;
+        }
+        Ok(total)
+    })
+}
+
+/// Copy the image onto the device with dd.
+///
+/// The device must already be unmounted; nothing is synced or verified here.
+pub fn write_iso(opts: WriteOptions) -> Result<WriteOutcome> {
+    let started = Instant::now();
+    let input = &opts.image;
+    let device = &opts.device;
+    // 0 = a streamed image of unknown length
+    let iso_bytes = match is_streamed(input) {
+        true => stream_size(input).unwrap_or(0),
+        false => image_size(input)?,
+    };
+    // Bytes dd will copy
+    let write_bytes = opts.range.map_or(iso_bytes, |r| r.len);
+    let decompressor = compression_tool(input);
+    let streaming = opts.hash_source;
+    let read_ahead = opts.reads_ahead();
+
+    if device.is_file_target() && opts.range.is_none() {
+        fs::File::create(&device.path)
+            .and_then(|f| f.set_len(iso_bytes))
+            .with_context(|| format!("Cannot create target file {}", device.path))?;
+    }
+
+    // Last check before dd opens the node — unmounting can take a while
+    revalidate_device(&RealSysfs, device)?;
+
+    log::info!("Running: {}", opts.command_line());
+    opts.progress.start(write_bytes);
+
+    // Compressed images are streamed through the decompressor into dd,
+    // downloads through curl
+    let source_tool = match decompressor {
+        _ if is_url(input) => Some("curl"),
+        tool => tool,
+    };
+    let mut unpack = match source_tool {
+        Some(tool) => {
+            let mut cmd = Command::new(tool);
+            match tool {
+                "curl" => cmd.args(["-fsSL", "--proto", "=http,https"]),
+                _ => cmd.arg("-dc"),
+            };
+            Some(
+                cmd.arg(input)
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("Failed to launch {} — is it installed?", tool))?,
+            )
+        }
+        None => None,
+    };
+    // --rate-limit: pv sits between the source and dd and paces it
+    let mut pacer = match opts.rate_limit {
+        Some(rate) => {
+            let mut cmd = Command::new("pv");
+            cmd.args(["-q", "-L", &rate.to_string()]);
+            let stdin = match unpack.as_mut() {
+                Some(u) => Stdio::from(u.stdout.take().unwrap()),
+                None if is_stdin_image(input) => Stdio::inherit(),
+                None => {
+                    cmd.arg(input);
+                    Stdio::null()
+                }
+            };
+            Some(
+                cmd.stdin(stdin)
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .context("Failed to launch pv — is it installed?")?,
+            )
+        }
+        None => None,
+    };
+    let dd_stdin = match pacer.as_mut().or(unpack.as_mut()) {
+        _ if streaming || read_ahead => Stdio::piped(),
+        Some(u) => Stdio::from(u.stdout.take().unwrap()),
+        None if is_stdin_image(input) => Stdio::inherit(),
+        None => Stdio::null(),
+    };
+
+    let mut child = Command::new(dd_bin())
+        .args(opts.dd_args())
+        .stdin(dd_stdin)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to launch dd — is it installed?")?;
+    track_child(child.id(), true);
+
+    // We feed dd ourselves when hashing on the way in or reading ahead
+    let chunk = opts.block_size.min(16 << 20) as usize;
+    let mut source: Option<Box<dyn std::io::Read + Send>> = match pacer.as_mut().or(unpack.as_mut()) {
+        _ if !streaming && !read_ahead => None,
+        Some(u) => Some(Box::new(u.stdout.take().unwrap())),
+        None if is_stdin_image(input) => Some(Box::new(std::io::stdin())),
+        None => Some(Box::new(fs::File::open(input)
+            .with_context(|| format!("Cannot read image: {}", input.display()))?)),
+    };
+    if read_ahead {
+        source = source.map(|s| {
+            Box::new(ReadAhead::spawn(s, chunk, opts.read_ahead, opts.progress.clone())) as Box<dyn std::io::Read + Send>
+        });
+    }
+    let (hasher, feeder) = match source {
+        Some(s) if streaming => (Some(spawn_md5_tee(s, child.stdin.take().unwrap(), chunk)), None),
+        Some(s) => (None, Some(spawn_feed(s, child.stdin.take().unwrap()))),
+        None => (None, None),
+    };
+
+    // Without oflag=sync dd only fills the page cache; flushing as we go keeps
+    // the reported progress close to what has really reached the stick
+    let sync_every = opts.periodic_sync();
+    let flusher = sync_every.and_then(|_| {
+        fs::File::open(&device.path)
+            .map_err(|e| log::warn!("periodic sync disabled, cannot open {}: {}", device.path, e))
+            .ok()
+    });
+    let mut next_sync = sync_every.unwrap_or(u64::MAX);
+    // Device offset of byte 0 of the write, and how far the page cache has been dropped
+    let base = opts.range.map_or(0, |r| r.offset);
+    let mut dropped = 0;
+
+    let mut stderr = child.stderr.take();
+    let position = Arc::new(AtomicU64::new(0));
+    let (pos, progress) = (position.clone(), opts.progress.clone());
+    let on_bytes = move |b| {
+        if let (Some(f), Some(every)) = (&flusher, sync_every) {
+            if b >= next_sync {
+                if let Err(e) = f.sync_data() {
+                    log::warn!("fdatasync at {} bytes failed: {}", b, e);
+                } else {
+                    drop_cached(f, base + dropped, b - dropped);
+                    dropped = b;
+                }
+                next_sync = b + every;
+            }
+        }
+        pos.store(b, Ordering::SeqCst);
+        progress.bytes(b);
+    };
+
+    let write_done = Arc::new(AtomicBool::new(false));
+    let polled = opts.polls_proc_io();
+    if opts.dd_status_none && !polled {
+        log::warn!("/proc/<pid>/io unreadable — following dd's status=progress instead");
+    }
+    let (parser, poller) = if polled {
+        (None, Some(spawn_proc_io_progress(child.id(), opts.running.clone(), write_done.clone(), on_bytes)))
+    } else {
+        (Some(spawn_dd_progress(stderr.take().unwrap(), opts.running.clone(), on_bytes)), None)
+    };
+    let watchdog = opts.timeout
+        .map(|t| spawn_stall_watchdog(position.clone(), t, write_done.clone()));
+
+    let status = child.wait().context("dd process error")?;
+    untrack_child();
+    write_done.store(true, Ordering::SeqCst);
+    let stalled = watchdog.is_some_and(|w| w.join().unwrap_or(false));
+    if let Some(p) = poller {
+        p.join().ok();
+    }
+    let mut dd_messages = parser.and_then(|p| p.join().ok()).unwrap_or_default();
+    log::info!("dd exited with {:?}", status.code());
+    if let Some(mut err) = stderr {
+        // status=none still reports errors on stderr
+        let mut msg = String::new();
+        std::io::Read::read_to_string(&mut err, &mut msg).ok();
+        dd_messages.extend(msg.lines().map(str::trim).filter(|l| l.starts_with("dd:")).map(String::from));
+    }
+    for m in &dd_messages {
+        log::warn!("{}", m);
+    }
+    // A decompressed or streamed image is only as long as it turns out to
+    // be — dd's final count is the real length, the metadata was an estimate.
+    // /proc/<pid>/io is only an estimate itself, so that needs dd's summary
+    // (unless there is nothing better).
+    let measured = (decompressor.is_some() || is_streamed(input)) && opts.range.is_none();
+    let written = match position.load(Ordering::SeqCst) {
+        n if measured && n > 0 && (!polled || write_bytes == 0) => n,
+        _ => write_bytes,
+    };
+    // Only trust the streamed hash if it covered the whole image
+    let source_md5 = hasher
+        .and_then(|h| h.join().ok())
+        .and_then(|r| r.map_err(|e| log::warn!("streaming hash failed: {}", e)).ok())
+        .filter(|(_, n)| *n == written)
+        .map(|(md5, _)| md5);
+    let unpack_ok = match unpack.as_mut() {
+        Some(u) => u.wait().map(|s| s.success()).unwrap_or(false),
+        None => true,
+    };
+    // A partial write stops reading early, which ends pv with SIGPIPE
+    let pace_ok = match pacer.as_mut() {
+        Some(p) => p.wait().is_ok_and(|s| s.success()) || opts.range.is_some(),
+        None => true,
+    };
+    let feed_ok = match feeder.map(|f| f.join()) {
+        Some(Ok(Err(e))) => {
+            log::warn!("read-ahead: {}", e.report());
+            false
+        }
+        Some(Err(_)) => false,
+        _ => true,
+    };
+
+    if !opts.running.load(Ordering::SeqCst) {
+        return Err(burn_err!("Interrupted — the USB holds a partial image"));
+    }
+
+    if stalled {
+        return Err(burn_err!(
+            "No write progress for {}s at {} — dd was stopped.\n\
+             The USB stick or its controller is probably failing; \
+             try another port or another drive.",
+            opts.timeout.unwrap_or_default().as_secs(),
+            format_size(position.load(Ordering::SeqCst))
+        ));
+    }
+
+    if !status.success() {
+        // dd prints its "N bytes copied" summary even when it fails
+        let reached = position.load(Ordering::SeqCst);
+        let end = opts.range.map_or(0, |r| r.offset) + reached;
+        let full = !device.is_file_target() && end + opts.block_size >= device.size;
+        let cause = DdError { code: status.code(), messages: dd_messages };
+        return Err(BurnError::WriteFailed { code: status.code(), dd: cause }).context(format!(
+            "dd failed (exit code {}) — wrote approximately {}{} before failing{}.\n\
+             \nTroubleshooting:\n\
+             • Run with sudo or as root\n\
+             • Make sure USB is properly connected\n\
+             • Try: sudo burn write -i ubuntu.iso",
+            status.code().unwrap_or(-1),
+            format_size(reached),
+            match write_bytes {
+                0 => String::new(),
+                n => format!(" of {}", format_size(n)),
+            },
+            if full {
+                " — the device is full, the image does not fit"
+            } else if reached > 0 {
+                " — a stick that fails partway through is often flaky"
+            } else {
+                ""
+            }
+        ));
+    }
+
+    if !unpack_ok && is_url(input) {
+        return Err(burn_err!(
+            "The download of {} failed or was cut short — the USB holds a partial image",
+            input.display()
+        ));
+    }
+    if !unpack_ok {
+        return Err(burn_err!(
+            "{} could not decompress {} — the image may be corrupt or truncated",
+            decompressor.unwrap_or("decompressor"),
+            input.display()
+        ));
+    }
+    if !pace_ok {
+        return Err(burn_err!("pv (--rate-limit) stopped early — the USB holds a partial image"));
+    }
+    if !feed_ok {
+        return Err(burn_err!(
+            "Reading {} failed partway — the USB holds a partial image",
+            input.display()
+        ));
+    }
+
+    if written != write_bytes {
+        log::info!("{}: {} bytes decompressed (metadata said {})", input.display(), written, write_bytes);
+    }
+    opts.progress.bytes(written);
+    Ok(WriteOutcome {
+        bytes_written: written,
+        source_md5,
+        duration: started.elapsed(),
+    })
+}
+
+// ─────────────────────────────────────────────
+//  DEVICE MARK — fingerprint in the last sector
+// ─────────────────────────────────────────────
+
+/// First line of a mark; anything else in the last sector isn't ours
+const MARK_MAGIC: &str = "BURNENGINE-MARK v1";
+
+/// What was written to a stick, kept in its very last logical sector. That
+/// sector lies past the end of any image that fits with room to spare, so
+/// the mark never overlaps image data.
+///
+/// Layout: `MARK_MAGIC`, then `key=value` lines (name, md5, size, written —
+/// unix seconds, and `protected=1` for reference media), zero-padded to the
+/// sector size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceMark {
+    /// Image file name, without its directory
+    pub name: String,
+    /// MD5 of the image file as given (compressed, if it was)
+    pub md5: String,
+    /// Bytes written to the device
+    pub size: u64,
+    /// When the mark was written, in unix seconds
+    pub written: u64,
+    /// `--protect`: reference media, to be overwritten only on purpose
+    pub protected: bool,
+}
+
+impl DeviceMark {
+    fn encode(&self, sector: usize) -> Result<Vec<u8>> {
+        let mut text = format!(
+            "{}\nname={}\nmd5={}\nsize={}\nwritten={}\n",
+            MARK_MAGIC, self.name.replace('\n', " "), self.md5, self.size, self.written
+        );
+        if self.protected {
+            text.push_str("protected=1\n");
+        }
+        if text.len() > sector {
+            return Err(burn_err!("mark doesn't fit in a {}-byte sector (image name too long?)", sector));
+        }
+        let mut buf = text.into_bytes();
+        buf.resize(sector, 0);
+        Ok(buf)
+    }
+
+    fn decode(buf: &[u8]) -> Option<DeviceMark> {
+        let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        let text = std::str::from_utf8(&buf[..end]).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != MARK_MAGIC {
+            return None;
+        }
+        let fields: Vec<(&str, &str)> = lines.filter_map(|l| l.split_once('=')).collect();
+        let get = |k: &str| fields.iter().find(|(key, _)| *key == k).map(|(_, v)| v.to_string());
+        Some(DeviceMark {
+            name: get("name")?,
+            md5: get("md5")?,
+            size: get("size")?.parse().ok()?,
+            written: get("written")?.parse().ok()?,
+            protected: get("protected").as_deref() == Some("1"),
+        })
+    }
+
+    /// Same image, same length — the device doesn't need a reflash
+    pub fn matches(&self, other: &DeviceMark) -> bool {
+        self.name == other.name && self.md5 == other.md5 && self.size == other.size
+    }
+}
+
+/// Byte offset of the mark sector; None when an image of `image_bytes`
+/// leaves no slack before it
+pub fn mark_offset(device: &UsbDevice, image_bytes: u64) -> Option<u64> {
+    let sector = device.logical_sector_size.max(512);
+    let offset = device.size.checked_sub(sector)?;
+    (image_bytes <= offset).then_some(offset)
+}
+
+/// The mark in the last sector, if there is one
+pub fn read_mark(device: &UsbDevice) -> Result<Option<DeviceMark>> {
+    use std::io::{Read, Seek, SeekFrom};
+    let Some(offset) = mark_offset(device, 0) else { return Ok(None) };
+    let mut buf = vec![0u8; device.logical_sector_size.max(512) as usize];
+    let mut f = fs::File::open(&device.path)
+        .with_context(|| format!("Cannot read {}", device.path))?;
+    f.seek(SeekFrom::Start(offset))
+        .and_then(|_| f.read_exact(&mut buf))
+        .with_context(|| format!("Cannot read the last sector of {}", device.path))?;
+    Ok(DeviceMark::decode(&buf))
+}
+
+/// Write `mark` into the last sector. Refuses when the image reaches it.
+pub fn write_mark(device: &UsbDevice, mark: &DeviceMark) -> Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+    let offset = mark_offset(device, mark.size).ok_or_else(|| burn_err!(
+        "the image fills {} up to its last sector — no slack space for a mark",
+        device.path
+    ))?;
+    let buf = mark.encode(device.logical_sector_size.max(512) as usize)?;
+    let mut f = fs::OpenOptions::new()
+        .write(true)
+        .open(&device.path)
+        .with_context(|| format!("Cannot open {} to write the mark", device.path))?;
+    f.seek(SeekFrom::Start(offset))
+        .and_then(|_| f.write_all(&buf))
+        .and_then(|_| f.sync_all())
+        .with_context(|| format!("Cannot write the mark to {}", device.path))?;
+    log::info!("Wrote mark at byte {} of {}: {:?}", offset, device.path, mark);
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+//  VERIFY
+// ─────────────────────────────────────────────
+
+/// `kind` hash of exactly the first `verify_bytes` of the device, hashed in-process
+/// (plus one per `chunk` bytes, if given).
+/// Reads whole `block_size` chunks, then one short read for the remainder.
+pub fn device_hash(
+    device: &UsbDevice,
+    kind: HashKind,
+    verify_bytes: u64,
+    block_size: u64,
+    chunk: Option<u64>,
+    progress: &dyn Progress,
+    running: Arc<AtomicBool>,
+) -> Result<ReadHashes> {
+    use std::io::Read;
+    log::info!("Reading back {} bytes of {} (bs={})", verify_bytes, device.path, block_size);
+    let mut dev = fs::File::open(&device.path)
+        .with_context(|| format!("cannot open {}", device.path))?;
+
+    // Whole logical sectors per read, even with a bs below a 4K sector
+    let block_size = block_size.max(device.logical_sector_size);
+    let mut hasher = ChunkedHasher::new(kind, chunk);
+    let mut buf = vec![0u8; block_size as usize];
+    let mut done = 0u64;
+    progress.start(verify_bytes);
+    while done < verify_bytes {
+        if !running.load(Ordering::SeqCst) {
+            return Err(burn_err!("interrupted"));
+        }
+        let want = (verify_bytes - done).min(block_size) as usize;
+        dev.read_exact(&mut buf[..want])
+            .with_context(|| format!("read error on {} at byte {}", device.path, done))?;
+        hasher.update(&buf[..want]);
+        done += want as u64;
+        progress.bytes(done);
+    }
+    Ok(hasher.finish())
+}
+
+/// Copy `source` into dd's stdin, hashing the same bytes on the way → (md5, bytes)
+pub fn spawn_md5_tee(
+    mut source: Box<dyn std::io::Read + Send>,
+    mut sink: std::process::ChildStdin,
+    chunk: usize,
+) -> thread::JoinHandle<Result<(String, u64)>> {
+    use std::io::{Read, Write};
+    thread::spawn(move || {
+        let mut hasher = Hasher::new(HashKind::Md5);
+        let mut buf = vec![0u8; chunk];
+        let mut total = 0u64;
+        loop {
+            let n = source.read(&mut buf).context("Cannot read image")?;
+            if n == 0 {
+                break;
+            }
+            sink.write_all(&buf[..n]).context("dd stopped reading")?;
+            hasher.update(&buf[..n]);
+            total += n as u64;
+        }
+        Ok((hasher.finish(), total))
+    })
+}
+
+/// Bytes the verify pass hashes on both sides — never past the end of the device
+pub fn verify_len(iso_bytes: u64, device_size: u64) -> u64 {
+    iso_bytes.min(device_size)
+}
+
+/// Hash of the first `bytes` of the image's logical (decompressed) stream
+/// (plus one per `chunk` bytes, if given)
+pub fn image_hash(
+    path: &Path,
+    kind: HashKind,
+    bytes: u64,
+    chunk: Option<u64>,
+    progress: &dyn Progress,
+) -> Result<ReadHashes> {
+    let (hashes, hashed) = hash_reader(&mut open_image(path)?, kind, bytes, chunk, progress)?;
+    if hashed < bytes {
+        return Err(burn_err!(
+            "{} ended after {} of {} bytes — the image may be truncated",
+            path.display(), hashed, bytes
+        ));
+    }
+    Ok(hashes)
+}
+
+/// Offset of the first byte where the logical (decompressed) streams of `a`
+/// and `b` differ within `len` bytes from `from` — None if that range matches.
+/// Everything before `from` is read and skipped.
+pub fn first_difference(a: &Path, b: &Path, from: u64, len: u64) -> Result<Option<u64>> {
+    use std::io::Read;
+    let (mut ra, mut rb) = (open_image(a)?, open_image(b)?);
+    for (r, path) in [(&mut ra, a), (&mut rb, b)] {
+        std::io::copy(&mut r.by_ref().take(from), &mut std::io::sink())
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+    }
+    let mut buf_a = vec![0u8; 1 << 20];
+    let mut buf_b = vec![0u8; 1 << 20];
+    let mut done = 0u64;
+    while done < len {
+        let want = (len - done).min(buf_a.len() as u64) as usize;
+        ra.read_exact(&mut buf_a[..want])
+            .with_context(|| format!("{} ended early", a.display()))?;
+        rb.read_exact(&mut buf_b[..want])
+            .with_context(|| format!("{} ended early", b.display()))?;
+        if let Some(i) = buf_a[..want].iter().zip(&buf_b[..want]).position(|(x, y)| x != y) {
+            return Ok(Some(from + done + i as u64));
+        }
+        done += want as u64;
+    }
+    Ok(None)
+}
+
+/// Up to `len` bytes of the logical stream of `path` starting at `from`
+/// (fewer if it ends first)
+pub fn read_range(path: &Path, from: u64, len: u64) -> Result<Vec<u8>> {
+    use std::io::Read;
+    let mut r = open_image(path)?;
+    std::io::copy(&mut r.by_ref().take(from), &mut std::io::sink())
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    let mut out = Vec::with_capacity(len as usize);
+    r.take(len)
+        .read_to_end(&mut out)
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    Ok(out)
+}
+
+// ─────────────────────────────────────────────
+//  CHILD PROCESSES
+// ─────────────────────────────────────────────
+
+/// The dd currently running, so an interrupt can stop it instead of orphaning it
+struct ActiveChild {
+    pid: u32,
+    /// True if it writes to the device (interrupting leaves a partial image)
+    writing: bool,
+}
+
+static ACTIVE_CHILD: Mutex<Option<ActiveChild>> = Mutex::new(None);
+
+pub fn track_child(pid: u32, writing: bool) {
+    if let Ok(mut slot) = ACTIVE_CHILD.lock() {
+        *slot = Some(ActiveChild { pid, writing });
+    }
+}
+
+pub fn untrack_child() {
+    if let Ok(mut slot) = ACTIVE_CHILD.lock() {
+        *slot = None;
+    }
+}
+
+/// SIGTERM the tracked child and give it a moment to exit
+pub fn stop_active_child() -> Option<bool> {
+    let child = ACTIVE_CHILD.lock().ok()?.take()?;
+    log::warn!("Interrupt: sending SIGTERM to pid {}", child.pid);
+    let _ = Command::new("kill")
+        .args(["-TERM", &child.pid.to_string()])
+        .status();
+
+    let proc_dir = format!("/proc/{}", child.pid);
+    for _ in 0..30 {
+        if !std::path::Path::new(&proc_dir).exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Some(child.writing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// A synthetic /sys and /dev: file contents, symlinks as written, and
+    /// where each path canonicalizes to
+    #[derive(Default)]
+    struct FakeSysfs {
+        block: Vec<String>,
+        files: HashMap<PathBuf, String>,
+        links: HashMap<PathBuf, PathBuf>,
+        canonical: HashMap<PathBuf, PathBuf>,
+        dev_nodes: Vec<String>,
+        /// "major:minor" of the /dev nodes that have one
+        node_ids: HashMap<String, String>,
+    }
+
+    impl FakeSysfs {
+        /// /sys/block/<name>, its `device` link resolving to `device_dir`,
+        /// and a /dev node
+        fn disk(&mut self, name: &str, device_dir: &str, removable: bool, sectors: u64) -> &mut Self {
+            let block = Path::new("/sys/block").join(name);
+            self.block.push(name.to_string());
+            self.canonical.insert(block.join("device"), PathBuf::from(device_dir));
+            self.files.insert(block.join("removable"), if removable { "1" } else { "0" }.into());
+            self.files.insert(block.join("size"), sectors.to_string());
+            self.dev_nodes.push(format!("/dev/{}", name));
+            self
+        }
+
+        /// `<dir>/subsystem` → ../bus/<subsystem>, as the kernel links it
+        fn bus(&mut self, dir: &str, subsystem: &str) -> &mut Self {
+            let target = PathBuf::from(format!("../../../bus/{}", subsystem));
+            self.links.insert(Path::new(dir).join("subsystem"), target);
+            self
+        }
+
+        fn file(&mut self, path: &str, value: &str) -> &mut Self {
+            self.files.insert(PathBuf::from(path), value.to_string());
+            self
+        }
+
+        /// "major:minor" in sysfs `dev` and on the /dev node alike
+        fn dev(&mut self, name: &str, id: &str) -> &mut Self {
+            self.node_ids.insert(format!("/dev/{}", name), id.to_string());
+            self.file(&format!("/sys/block/{}/dev", name), id)
+        }
+
+        /// `path` with a leading `device` link followed, as the kernel would
+        fn resolve(&self, path: &Path) -> PathBuf {
+            self.canonical
+                .iter()
+                .find_map(|(link, real)| path.strip_prefix(link).ok().map(|rest| real.join(rest)))
+                .unwrap_or_else(|| path.to_path_buf())
+        }
+    }
+
+    impl SysfsProvider for FakeSysfs {
+        fn list_block(&self) -> Vec<String> {
+            self.block.clone()
+        }
+
+        fn read_file(&self, path: &Path) -> Option<String> {
+            self.files.get(&self.resolve(path)).map(|s| s.trim().to_string())
+        }
+
+        fn read_link(&self, path: &Path) -> Option<PathBuf> {
+            self.links.get(&self.resolve(path)).cloned()
+        }
+
+        fn canonicalize(&self, path: &Path) -> Option<PathBuf> {
+            Some(self.resolve(path)).filter(|p| self.canonical.values().any(|real| p.starts_with(real)))
+        }
+
+        fn dev_node_exists(&self, dev_path: &str) -> bool {
+            self.dev_nodes.iter().any(|n| n == dev_path)
+        }
+
+        fn dev_node_id(&self, dev_path: &str) -> Option<String> {
+            self.node_ids.get(dev_path).cloned()
+        }
+
+        fn list_by_id(&self) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    const STICK: &str = "/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host6/target6:0:0/6:0:0:0";
+    const SATA: &str = "/sys/devices/pci0000:00/0000:00:17.0/ata1/host0/target0:0:0/0:0:0:0";
+    const OPTICAL: &str = "/sys/devices/pci0000:00/0000:00:17.0/ata2/host1/target1:0:0/1:0:0:0";
+    const SD_CARD: &str = "/sys/devices/pci0000:00/0000:00:14.5/mmc_host/mmc0/mmc0:aaaa";
+    const LEFTOVER: &str = "/sys/devices/pci0000:00/0000:00:14.0/usb2/2-2/2-2:1.0/host7/target7:0:0/7:0:0:0";
+
+    /// A 16 GB stick, an internal SATA disk, a DVD drive, an SD card and a
+    /// 32 MB USB leftover (a card reader's empty-ish slot)
+    fn machine() -> FakeSysfs {
+        let mut sys = FakeSysfs::default();
+        sys.disk("sda", SATA, false, 976_773_168)
+            .bus(SATA, "scsi")
+            .disk("sdb", STICK, true, 31_250_000)
+            .bus(STICK, "scsi")
+            .bus("/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0", "usb")
+            .file(&format!("{}/vendor", STICK), "SanDisk ")
+            .file(&format!("{}/model", STICK), "Ultra           ")
+            .disk("sr0", OPTICAL, true, 2_097_151)
+            .bus(OPTICAL, "scsi")
+            .disk("mmcblk0", SD_CARD, true, 62_333_952)
+            .bus(SD_CARD, "mmc")
+            .disk("sdc", LEFTOVER, true, 62_500)
+            .bus(LEFTOVER, "scsi")
+            .bus("/sys/devices/pci0000:00/0000:00:14.0/usb2/2-2/2-2:1.0", "usb");
+        sys
+    }
+
+    #[test]
+    fn only_the_usb_stick_is_detected() {
+        let sys = machine();
+        let drives = detect_usb_drives(&sys);
+        assert_eq!(drives.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(), ["sdb"]);
+        assert_eq!(drives[0].path, "/dev/sdb");
+        assert_eq!(drives[0].size, 16_000_000_000);
+        assert_eq!(drives[0].model, "SanDisk Ultra");
+    }
+
+    #[test]
+    fn each_disk_is_excluded_for_its_own_reason() {
+        let sys = machine();
+        let why = |name: &str| exclusion_reason(&sys, &probe_block_device(&sys, name));
+        assert_eq!(why("sda"), Some(Exclusion::NotRemovable));
+        assert_eq!(why("sdb"), None);
+        assert_eq!(why("sr0"), Some(Exclusion::Optical));
+        assert_eq!(why("mmcblk0"), Some(Exclusion::Transport("mmc".into())));
+        assert_eq!(why("sdc"), Some(Exclusion::TooSmall(32_000_000)));
+    }
+
+    #[test]
+    fn transport_of_each_disk() {
+        let sys = machine();
+        let transport = |name: &str| detect_transport(&sys, &format!("/sys/block/{}", name));
+        assert_eq!(transport("sda"), "ata");
+        assert_eq!(transport("sdb"), "usb");
+        assert_eq!(transport("sr0"), "ata");
+        assert_eq!(transport("mmcblk0"), "mmc");
+        assert_eq!(transport("sdc"), "usb");
+        assert_eq!(transport("nvme0n1"), "unknown");
+    }
+
+    #[test]
+    fn transport_walks_up_through_uas_and_hubs() {
+        // UAS: scsi device → target → host → interface, and the first usb
+        // link three hubs up
+        let uas = "/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1.4/2-1.4.2/2-1.4.2:1.0/host9/target9:0:0/9:0:0:0";
+        let mut sys = FakeSysfs::default();
+        sys.disk("sdd", uas, true, 31_250_000)
+            .bus(uas, "scsi")
+            .bus("/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1.4/2-1.4.2/2-1.4.2:1.0/host9", "scsi")
+            .bus("/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1.4/2-1.4.2/2-1.4.2:1.0", "usb")
+            .bus("/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1.4/2-1.4.2", "usb")
+            .bus("/sys/devices/pci0000:00/0000:00:14.0", "pci");
+        assert_eq!(detect_transport(&sys, "/sys/block/sdd"), "usb");
+        assert_eq!(detect_usb_drives(&sys).len(), 1);
+    }
+
+    #[test]
+    fn transport_finds_nvme_below_pci() {
+        let nvme = "/sys/devices/pci0000:00/0000:00:1d.0/0000:3c:00.0/nvme/nvme0/nvme0n1";
+        let mut sys = FakeSysfs::default();
+        sys.disk("nvme0n1", nvme, false, 1_000_215_216)
+            .bus("/sys/devices/pci0000:00/0000:00:1d.0/0000:3c:00.0/nvme/nvme0", "nvme")
+            .bus("/sys/devices/pci0000:00/0000:00:1d.0/0000:3c:00.0", "pci");
+        assert_eq!(detect_transport(&sys, "/sys/block/nvme0n1"), "nvme");
+    }
+
+    #[test]
+    fn transport_falls_back_to_the_path_without_subsystem_links() {
+        let mut sys = FakeSysfs::default();
+        sys.disk("sde", STICK, true, 31_250_000).disk("sda", SATA, false, 976_773_168);
+        assert_eq!(detect_transport(&sys, "/sys/block/sde"), "usb");
+        assert_eq!(detect_transport(&sys, "/sys/block/sda"), "ata");
+    }
+
+    /// sdb as the scan saw it
+    fn scanned_stick(sys: &FakeSysfs) -> UsbDevice {
+        probe_block_device(sys, "sdb")
+    }
+
+    fn changed_because(result: Result<()>, why: &str) -> bool {
+        matches!(result, Err(BurnError::Other(m)) if m.starts_with("Device changed") && m.contains(why))
+    }
+
+    #[test]
+    fn revalidate_accepts_the_same_stick() {
+        // No "dev" attribute: the /dev node's own numbers aren't compared
+        let sys = machine();
+        assert!(revalidate_device(&sys, &scanned_stick(&sys)).is_ok());
+
+        let mut sys = machine();
+        sys.dev("sdb", "8:16");
+        assert!(revalidate_device(&sys, &scanned_stick(&sys)).is_ok());
+    }
+
+    #[test]
+    fn revalidate_refuses_a_swapped_stick() {
+        let mut sys = machine();
+        let stick = scanned_stick(&sys);
+        sys.file("/sys/block/sdb/size", "62500000");
+        assert!(changed_because(revalidate_device(&sys, &stick), "size was 16000000000 bytes, now 32000000000"));
+
+        let mut sys = machine();
+        sys.file(&format!("{}/model", STICK), "Cruzer Blade");
+        assert!(changed_because(revalidate_device(&sys, &stick), "model was 'SanDisk Ultra', now 'SanDisk Cruzer Blade'"));
+
+        let mut sys = machine();
+        sys.file("/sys/block/sdb/dev", "8:16");
+        let stick = scanned_stick(&sys);
+        sys.file("/sys/block/sdb/dev", "8:32");
+        assert!(changed_because(revalidate_device(&sys, &stick), "major:minor was 8:16, now 8:32"));
+    }
+
+    #[test]
+    fn revalidate_refuses_a_node_that_is_another_device() {
+        let mut sys = machine();
+        sys.dev("sdb", "8:16");
+        let stick = scanned_stick(&sys);
+        // sysfs still agrees, but /dev/sdb was recreated for another disk
+        sys.node_ids.insert("/dev/sdb".into(), "8:32".into());
+        assert!(changed_because(revalidate_device(&sys, &stick), "/dev/sdb is 8:32 but the scanned drive was 8:16"));
+
+        sys.node_ids.remove("/dev/sdb");
+        assert!(changed_because(revalidate_device(&sys, &stick), "/dev/sdb is not a device node"));
+    }
+
+    #[test]
+    fn revalidate_refuses_an_unplugged_stick() {
+        let mut sys = machine();
+        let stick = scanned_stick(&sys);
+        sys.dev_nodes.retain(|n| n != "/dev/sdb");
+        assert!(changed_because(revalidate_device(&sys, &stick), "device node no longer exists"));
+
+        let mut sys = machine();
+        sys.files.remove(Path::new("/sys/block/sdb/size"));
+        assert!(changed_because(revalidate_device(&sys, &stick), "cannot read size"));
+    }
+
+    #[test]
+    fn revalidate_skips_file_targets() {
+        let file = UsbDevice { transport: "file".into(), path: "/tmp/out.img".into(), ..scanned_stick(&machine()) };
+        assert!(revalidate_device(&FakeSysfs::default(), &file).is_ok());
+    }
+
+    #[test]
+    fn verify_len_never_passes_the_device_end() {
+        assert_eq!(verify_len(4_000_000_000, 16_000_000_000), 4_000_000_000);
+        assert_eq!(verify_len(16_000_000_000, 16_000_000_000), 16_000_000_000);
+        // An image (wrongly) larger than the device is verified up to its end only
+        assert_eq!(verify_len(16_000_000_512, 16_000_000_000), 16_000_000_000);
+        assert_eq!(verify_len(0, 16_000_000_000), 0);
+    }
+
+    /// tests/fixtures: 300 001 bytes of "burn fixture\n", compressed
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    #[test]
+    fn decompressed_size_of_each_format() {
+        assert_eq!(decompressed_size(&fixture("image.img.gz")), Some(300_001));
+        // Written in 100 000-byte blocks: the index has four records to add up
+        assert_eq!(decompressed_size(&fixture("image.img.xz")), Some(300_001));
+        assert_eq!(decompressed_size(&fixture("image.img.zst")), Some(300_001));
+    }
+
+    #[test]
+    fn decompressed_size_unknown() {
+        // zstd fed from a pipe doesn't store the content size
+        assert_eq!(decompressed_size(&fixture("streamed.img.zst")), None);
+        // Not what the extension says
+        let fake = std::env::temp_dir().join(format!("burn-test-{}.img.gz", std::process::id()));
+        fs::write(&fake, vec![0u8; 4096]).unwrap();
+        assert_eq!(decompressed_size(&fake), None);
+        fs::remove_file(&fake).unwrap();
+        // Not compressed at all
+        assert_eq!(decompressed_size(&PathBuf::from(file!())), None);
+    }
+
+    #[test]
+    fn gzip_isize_wraps_at_4_gib() {
+        assert_eq!(gzip_size_from_isize(300_001, 634), 300_001);
+        // A 6 GiB image that compressed to 5 GiB: ISIZE holds 6 GiB mod 4 GiB
+        let six_gib = 6u64 << 30;
+        assert_eq!(gzip_size_from_isize(six_gib as u32, 5 << 30), six_gib);
+    }
+
+    const MIB: u64 = 1 << 20;
+
+    #[test]
+    fn write_range_resolves_against_the_image() {
+        assert_eq!(WriteRange::resolve(None, None, 700 * MIB).unwrap(), None);
+        assert_eq!(
+            WriteRange::resolve(Some(MIB), None, 700 * MIB).unwrap(),
+            Some(WriteRange { offset: MIB, len: 699 * MIB })
+        );
+        assert_eq!(
+            WriteRange::resolve(None, Some(4 * MIB), 700 * MIB).unwrap(),
+            Some(WriteRange { offset: 0, len: 4 * MIB })
+        );
+        let exact = WriteRange::resolve(Some(696 * MIB), Some(4 * MIB), 700 * MIB).unwrap().unwrap();
+        assert_eq!(exact.end(), 700 * MIB);
+    }
+
+    #[test]
+    fn write_range_refuses_ranges_off_the_image() {
+        assert!(WriteRange::resolve(Some(700 * MIB), None, 700 * MIB).is_err());
+        assert!(WriteRange::resolve(Some(MIB), Some(0), 700 * MIB).is_err());
+        assert!(WriteRange::resolve(Some(697 * MIB), Some(4 * MIB), 700 * MIB).is_err());
+    }
+
+    #[test]
+    fn write_range_in_whole_blocks_when_aligned() {
+        let range = WriteRange { offset: 8 * MIB, len: 12 * MIB };
+        assert_eq!(range.dd_args(4 * MIB), ["skip=2", "seek=2", "count=3", "conv=notrunc"]);
+        assert_eq!(range.dd_args(512), ["skip=16384", "seek=16384", "count=24576", "conv=notrunc"]);
+    }
+
+    #[test]
+    fn write_range_in_bytes_when_not_aligned() {
+        let range = WriteRange { offset: 446, len: 66 };
+        assert_eq!(
+            range.dd_args(4 * MIB),
+            [
+                "skip=446",
+                "seek=446",
+                "count=66",
+                "iflag=skip_bytes,count_bytes",
+                "oflag=seek_bytes",
+                "conv=notrunc",
+            ]
+        );
+        // Aligned offset, ragged length: still bytes, or the tail would be lost
+        assert_eq!(WriteRange { offset: 4 * MIB, len: MIB + 1 }.dd_args(4 * MIB)[2], format!("count={}", MIB + 1));
+    }
+
+    #[test]
+    fn dd_bytes_in_every_grouping() {
+        let copied = " (1.2 GB, 1.1 GiB) copied, 10 s, 123 MB/s";
+        for bytes in ["1234567168 bytes", "1,234,567,168 bytes", "1 234 567 168 bytes", "1\u{a0}234\u{a0}567\u{a0}168 bytes"] {
+            assert_eq!(parse_dd_bytes(&format!("{}{}", bytes, copied)), Some(1_234_567_168), "{}", bytes);
+        }
+        assert_eq!(parse_dd_bytes("1234567168 bytes"), Some(1_234_567_168));
+        assert_eq!(parse_dd_bytes("512 bytes copied, 0.001 s, 512 kB/s"), Some(512));
+    }
+
+    #[test]
+    fn dd_lines_without_a_byte_count() {
+        assert_eq!(parse_dd_bytes("2+0 records in"), None);
+        assert_eq!(parse_dd_bytes("dd: error writing '/dev/sdb': No space left on device"), None);
+        assert_eq!(parse_dd_bytes("bytes"), None);
+    }
+
+    #[test]
+    fn shell_quote_leaves_plain_words_alone() {
+        assert_eq!(shell_quote("/dev/sdb"), "/dev/sdb");
+        assert_eq!(shell_quote("bs=4M"), "bs=4M");
+        assert_eq!(shell_quote("iflag=skip_bytes,count_bytes"), "iflag=skip_bytes,count_bytes");
+    }
+
+    #[test]
+    fn shell_quote_quotes_everything_else() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("if=/home/me/My ISOs/ubuntu.iso"), "'if=/home/me/My ISOs/ubuntu.iso'");
+        assert_eq!(shell_quote("it's.iso"), "'it'\\''s.iso'");
+        assert_eq!(shell_quote("$HOME/`id`.iso"), "'$HOME/`id`.iso'");
+        assert_eq!(shell_quote("a;rm -rf ~"), "'a;rm -rf ~'");
+    }
+
+    #[test]
+    fn logical_sector_size_from_sysfs() {
+        let mut sys = machine();
+        assert_eq!(probe_block_device(&sys, "sdb").logical_sector_size, 512);
+        sys.file("/sys/block/sdb/queue/logical_block_size", "4096");
+        assert_eq!(probe_block_device(&sys, "sdb").logical_sector_size, 4096);
+        // Nonsense from a bridge falls back to 512
+        sys.file("/sys/block/sdb/queue/logical_block_size", "1000");
+        assert_eq!(probe_block_device(&sys, "sdb").logical_sector_size, 512);
+        sys.file("/sys/block/sdb/queue/logical_block_size", "256");
+        assert_eq!(probe_block_device(&sys, "sdb").logical_sector_size, 512);
+    }
+
+    #[test]
+    fn mark_goes_in_the_last_logical_sector() {
+        let size = 16_000_000_000;
+        let mut stick = UsbDevice::file_target(Path::new("stick.img"), size);
+        assert_eq!(mark_offset(&stick, 4_000_000_000), Some(size - 512));
+        assert_eq!(mark_offset(&stick, size - 512), Some(size - 512));
+        assert_eq!(mark_offset(&stick, size - 511), None);
+
+        stick.logical_sector_size = 4096;
+        assert_eq!(mark_offset(&stick, 4_000_000_000), Some(size - 4096));
+        assert_eq!(mark_offset(&stick, size - 4096), Some(size - 4096));
+        // Room for a 512-byte sector but not for a 4K one
+        assert_eq!(mark_offset(&stick, size - 512), None);
+    }
+
+    /// The USB device node of the stick in [`machine`], four levels above
+    /// the SCSI device
+    const STICK_USB: &str = "/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1";
+
+    /// sdb with the SCSI vendor/model pair and nothing else
+    fn bare_stick() -> FakeSysfs {
+        let mut sys = FakeSysfs::default();
+        sys.disk("sdb", STICK, true, 31_250_000);
+        sys
+    }
+
+    #[test]
+    fn model_from_the_scsi_vendor_and_model() {
+        let mut sys = bare_stick();
+        sys.file(&format!("{}/vendor", STICK), " SanDisk ").file(&format!("{}/model", STICK), "Ultra   Fit      ");
+        assert_eq!(read_device_model(&sys, "/sys/block/sdb").as_deref(), Some("SanDisk Ultra Fit"));
+
+        // Not repeated when the model already starts with it
+        sys.file(&format!("{}/vendor", STICK), "Kingston").file(&format!("{}/model", STICK), "Kingston DataTraveler 3.0");
+        assert_eq!(read_device_model(&sys, "/sys/block/sdb").as_deref(), Some("Kingston DataTraveler 3.0"));
+
+        // libata's placeholder isn't a vendor
+        sys.file(&format!("{}/vendor", STICK), "ATA     ").file(&format!("{}/model", STICK), "Samsung SSD 860");
+        assert_eq!(read_device_model(&sys, "/sys/block/sdb").as_deref(), Some("Samsung SSD 860"));
+    }
+
+    #[test]
+    fn model_from_the_usb_manufacturer_and_product() {
+        let mut sys = bare_stick();
+        // An empty SCSI model doesn't count
+        sys.file(&format!("{}/model", STICK), "")
+            .file(&format!("{}/manufacturer", STICK_USB), "SanDisk")
+            .file(&format!("{}/product", STICK_USB), "Cruzer Blade");
+        assert_eq!(read_device_model(&sys, "/sys/block/sdb").as_deref(), Some("SanDisk Cruzer Blade"));
+
+        let mut sys = bare_stick();
+        sys.file(&format!("{}/product", STICK_USB), "USB DISK 2.0");
+        assert_eq!(read_device_model(&sys, "/sys/block/sdb").as_deref(), Some("USB DISK 2.0"));
+    }
+
+    #[test]
+    fn model_falls_back_to_a_generic_name() {
+        let sys = bare_stick();
+        assert_eq!(read_device_model(&sys, "/sys/block/sdb"), None);
+        assert_eq!(read_model(&sys, "/sys/block/sdb"), "USB Drive");
+    }
+
+    #[test]
+    fn a_30_mb_image_on_a_16_gb_stick() {
+        let image = 30_000_000;
+        let sys = machine();
+        let stick = probe_block_device(&sys, "sdb");
+        // The 100 MB floor is for drives: the stick qualifies, the image needn't
+        assert_eq!(exclusion_reason(&sys, &stick), None);
+        assert_eq!(verify_len(image, stick.size), image);
+        assert_eq!(mark_offset(&stick, image), Some(stick.size - 512));
+        assert_eq!(
+            WriteRange::resolve(None, Some(4 * MIB), image).unwrap(),
+            Some(WriteRange { offset: 0, len: 4 * MIB })
+        );
+    }
+
+    #[test]
+    fn real_size_is_where_the_node_ends() {
+        let path = std::env::temp_dir().join(format!("burn-test-{}.img", std::process::id()));
+        fs::File::create(&path).unwrap().set_len(3 * MIB + 512).unwrap();
+        // Whatever the scan (or --assume-device-size) claimed
+        let claimed = UsbDevice::file_target(&path, 16_000_000_000);
+        assert_eq!(device_real_size(&claimed).unwrap(), 3 * MIB + 512);
+        fs::remove_file(&path).unwrap();
+        assert!(device_real_size(&claimed).is_err());
+    }
+
+    #[test]
+    fn device_hash_stops_at_the_image_end_on_4k_sectors() {
+        // Three whole 4K sectors and a partial one, then what an older image left behind
+        let image: Vec<u8> = (0..3 * 4096 + 1000).map(|i| (i % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("burn-test-4k-{}.img", std::process::id()));
+        fs::write(&path, [image.as_slice(), &[0xff; 8192]].concat()).unwrap();
+        let mut stick = UsbDevice::file_target(&path, image.len() as u64 + 8192);
+        stick.logical_sector_size = 4096;
+
+        let (want, _) = hash_reader(&mut image.as_slice(), HashKind::Md5, image.len() as u64, Some(4096), &NoProgress).unwrap();
+        let running = Arc::new(AtomicBool::new(true));
+        // A bs below the sector size is rounded up to it
+        let got = device_hash(&stick, HashKind::Md5, image.len() as u64, 512, Some(4096), &NoProgress, running).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(got.hash, want.hash);
+        assert_eq!(got.chunks, want.chunks);
+        assert_eq!(got.chunks.len(), 4);
+    }
+}
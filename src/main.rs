@@ -0,0 +1,5516 @@
+use anyhow::{anyhow, Context, Result};
+use burn_engine::{
+    self as engine, allowed_devices, compression_tool, dd_bin, dd_path_override,
+    decompressed_size, detect_all_block_devices, detect_usb_drives, device_filesystems,
+    device_hash, device_mounts, device_real_size, discard_device, exclusion_reason,
+    find_source_checksum, format_block_size, format_size, hash_file, image_hash, image_size,
+    inspect_device, inspect_iso, is_block_device, is_kernel_read_only, is_streamed, is_url,
+    is_write_protected, logical_size, mark_offset, raid_lvm_membership, read_mark,
+    read_partition_scheme, read_smart, read_through, revalidate_device, set_allowed_devices,
+    set_dd_path, set_read_only, set_units, shell_quote, spawn_dd_progress, stop_active_child,
+    stream_size, supports_discard, track_child, units, unmount_device, untrack_child,
+    verify_len, write_mark, zap_device, BurnError, DetectTimings, DeviceMark, DeviceScanner,
+    Exclusion, HashKind, ImageKind, PartitionScheme, Progress, ReadHashes, RealSysfs, SmartInfo,
+    Units, UsbDevice, WriteRange, CHECKPOINT_CHUNK, DEFAULT_BLOCK_SIZE, DEFAULT_SYNC_INTERVAL,
+    STDIN_IMAGE, ZAP_BYTES,
+};
+use clap::{CommandFactory, Parser, Subcommand,};
+use colored::*;
+use dialoguer::{Confirm, Select, theme::ColorfulTheme};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ─────────────────────────────────────────────
+//  CLI
+// ─────────────────────────────────────────────
+
+#[derive(Parser)]
+#[command(
+    name = "burn",
+    about = "🔥 BurnEngine USB — Real Linux ISO → USB Writer",
+    long_about = r#"
+╔══════════════════════════════════════════════════════════╗
+║         🔥  B U R N E N G I N E  U S B  v3.0  🔥        ║
+║         Real · Safe · Linux ISO to USB Writer            ║
+╚══════════════════════════════════════════════════════════╝
+
+Writes Linux ISO images directly to USB drives using dd.
+Only detects REMOVABLE drives — never touches internal disks."#,
+    after_long_help = "\
+Exit codes:
+  0  success
+  1  any other error
+  2  cancelled at a confirmation prompt
+  3  no (matching) USB drive found
+  4  the image doesn't fit on the drive
+  5  dd failed while writing or wiping
+  6  written, but verification failed
+  7  needs root and wasn't re-run with sudo
+  write --after-command: a non-zero exit of the command is passed through
+
+Environment:
+  BURN_DEFAULT_DEVICE  device used when --device is omitted (path, model: or serial:);
+                       if it isn't a detected USB drive, the drive picker is shown",
+    version,
+    propagate_version = true
+)]
+struct Cli {
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Dry-run: show what would happen without writing
+    #[arg(short = 'n', long, global = true)]
+    dry_run: bool,
+
+    /// Force: overwrite existing output files, skip image sanity prompts
+    #[arg(short, long, global = true)]
+    force: bool,
+
+    /// Quiet: no banner, panels or progress bars — just prompts, warnings, errors and the result
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Size units: si (GB, as printed on the stick) or iec (GiB, as the kernel counts)
+    #[arg(long, global = true, value_name = "si|iec", default_value = "si", value_parser = parse_units)]
+    units: Units,
+
+    /// Disable colors and emoji (also honored: NO_COLOR env var)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Use this dd binary instead of the one on PATH (e.g. gdd, busybox symlink)
+    #[arg(long, global = true, value_name = "PATH")]
+    dd_path: Option<PathBuf>,
+
+    /// Append a plain-text, timestamped log of this run to a file
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Emit newline-delimited JSON progress events on this file descriptor
+    /// (for GUI frontends) and hide the progress bars
+    #[arg(long, global = true, value_name = "FD")]
+    progress_fd: Option<i32>,
+
+    /// After a write or verify, save a JSON report of it to PATH — image and
+    /// hash, device, bytes, timings, result and exit status — even on failure
+    #[arg(long, global = true, value_name = "PATH")]
+    summary_json: Option<PathBuf>,
+
+    /// When output is not a terminal (CI, a log file), print a plain progress
+    /// line every SECS instead of animated bars
+    #[arg(long, global = true, value_name = "SECS", default_value_t = 10)]
+    progress_interval: u64,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+// Parsed once in main — the size of the Write variant doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Commands {
+    /// 🔥 Write ISO to USB drive
+    Write {
+        /// Image to write, as in `burn write ubuntu.iso /dev/sdb` — same as --input
+        #[arg(value_name = "IMAGE", conflicts_with = "input")]
+        image_pos: Option<PathBuf>,
+
+        /// Target device, as in `burn write ubuntu.iso /dev/sdb` — same as --device
+        #[arg(value_name = "DEVICE", conflicts_with_all = ["device", "target_file", "watch"])]
+        device_pos: Option<String>,
+
+        /// Image to write: .iso, .img (Raspberry Pi, Armbian…) or a .gz/.xz/.zst compressed one,
+        /// or an http(s):// URL streamed through curl
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// Read the image from standard input: curl … | burn write --stdin -d /dev/sdb.
+        /// Its size isn't known up front, so there is no fit check and no verify.
+        #[arg(long, conflicts_with_all = ["input", "image_pos"])]
+        stdin: bool,
+
+        /// Target USB device: /dev/sdb, a /dev/disk/by-id/… link, model:<text> or serial:<text>
+        /// — $BURN_DEFAULT_DEVICE or auto-detected if omitted
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// Verify MD5 checksum after write
+        #[arg(long)]
+        verify: bool,
+
+        /// Retry the verify readback this many times if reading the USB fails
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_VERIFY_RETRIES)]
+        verify_retries: u32,
+
+        /// Don't check the ISO against a .sha256 / .md5 / SHA256SUMS file next to it before writing
+        #[arg(long)]
+        no_source_verify: bool,
+
+        /// Read the whole image once before touching the USB, so a bad sector on the
+        /// source drive aborts the run instead of a half-finished write
+        #[arg(long)]
+        precheck_source: bool,
+
+        /// Hash the ISO while it is written so verify only has to re-read the USB (implies --verify)
+        #[arg(long)]
+        verify_streaming: bool,
+
+        /// Also hash every 256 MiB region of image and USB during verify and log them to
+        /// FILE as JSON lines, so a mismatch can be pinned to a region (implies --verify)
+        #[arg(long, value_name = "FILE")]
+        checkpoint: Option<PathBuf>,
+
+        /// Only verify the first SIZE of the image (e.g. 512M) — a quick smoke test,
+        /// or to skip trailing padding (implies --verify)
+        #[arg(long, value_name = "SIZE", value_parser = parse_verify_bytes)]
+        verify_bytes: Option<u64>,
+
+        /// After a passing verify, save the image MD5 to FILE as "<hash>  <name>",
+        /// checkable with md5sum -c (implies --verify; appends with --force)
+        #[arg(long, value_name = "FILE")]
+        write_checksum: Option<PathBuf>,
+
+        /// If verify fails, compare image and USB byte by byte to report the first
+        /// differing offset with a hexdump of both (implies --verify)
+        #[arg(long)]
+        diagnose_mismatch: bool,
+
+        /// dd block size (e.g. 512K, 1M, 4M, 16M) — must be a power of two
+        #[arg(long, default_value = "4M", value_parser = parse_block_size)]
+        block_size: u64,
+
+        /// Don't retry once with bs=1M when the write fails with an I/O error
+        #[arg(long)]
+        no_retry: bool,
+
+        /// After writing, add a data partition in the leftover space ("rest" or a size like 8G)
+        #[arg(long, value_name = "SIZE|rest", value_parser = parse_extra_partition)]
+        extra_partition: Option<ExtraPartition>,
+
+        /// Volume label for the --extra-partition filesystem: up to 11 ASCII characters,
+        /// none of " * + , . / : ; < = > ? [ \ ] | (FAT upper-cases it)
+        #[arg(long, value_name = "NAME", requires = "extra_partition", value_parser = parse_label)]
+        label: Option<String>,
+
+        /// Power off the USB after a successful write so it's safe to remove
+        #[arg(long)]
+        eject: bool,
+
+        /// Skip the write if the USB already holds an exact copy of the ISO
+        /// (byte-for-byte MD5 readback of the raw image — exact match only)
+        #[arg(long)]
+        skip_if_verified: bool,
+
+        /// After a successful write, record the image name, MD5 and time in the last
+        /// sector of the stick — slack space past the end of the image, never image data
+        #[arg(long, conflicts_with = "extra_partition")]
+        mark: bool,
+
+        /// After a successful write, flag the stick as reference media: a mark with
+        /// protected=1 in its last sector (implies --mark) plus the kernel read-only
+        /// flag (blockdev --setro). Later writes warn and ask again before overwriting.
+        /// The read-only flag resets when the stick is replugged; the mark stays.
+        #[arg(long, conflicts_with_all = ["extra_partition", "offset", "size", "assume_device_size", "target_file"])]
+        protect: bool,
+
+        /// Skip the write when the stick's mark (see --mark) names this exact image —
+        /// a cheap alternative to --skip-if-verified for sticks refreshed on a schedule
+        #[arg(long)]
+        check_mark: bool,
+
+        /// Zero the first and last 1 MiB of the USB before writing, clearing stale
+        /// MBR/GPT tables (a leftover backup GPT can stop a reflashed stick from booting).
+        /// On by default for hybrid ISOs.
+        #[arg(long, conflicts_with_all = ["no_zap", "offset", "size"])]
+        zap: bool,
+
+        /// Don't clear old partition tables before writing a hybrid ISO
+        #[arg(long)]
+        no_zap: bool,
+
+        /// Discard (TRIM) the whole USB with blkdiscard before writing — a clean
+        /// slate that speeds up the write on decent controllers. Skipped when
+        /// the stick doesn't support it.
+        #[arg(long, conflicts_with_all = ["offset", "size"])]
+        discard: bool,
+
+        /// Extra dd conv= flags, comma-separated: notrunc, fsync, fdatasync
+        /// (older isohybrid images may need notrunc)
+        #[arg(long, value_name = "FLAGS", value_parser = parse_dd_conv)]
+        dd_conv: Option<String>,
+
+        /// dd oflag= flags, comma-separated: sync, dsync, direct, nocache — or "none".
+        /// Defaults to sync; some MBR/ISO hybrids write more reliably with none or direct.
+        /// Partial writes add conv=notrunc / oflag=seek_bytes on top of these.
+        #[arg(long, value_name = "FLAGS", default_value = "sync", value_parser = parse_dd_oflag)]
+        dd_oflag: String,
+
+        /// Write with O_DIRECT (oflag=direct), bypassing the page cache entirely — for
+        /// low-memory machines. The block size, --offset and --size must be multiples of
+        /// the device's sector size. Falls back to oflag=sync if O_DIRECT is refused.
+        #[arg(long, conflicts_with = "dd_oflag")]
+        direct: bool,
+
+        /// With --dd-oflag none: fdatasync the USB every SIZE written, and drop what was
+        /// flushed from the page cache, so progress tracks the stick and RAM stays free
+        /// ("0" = only the final sync)
+        #[arg(long, value_name = "SIZE", default_value = "64M", value_parser = parse_size)]
+        sync_interval: u64,
+
+        /// dd status= mode. "none" keeps dd quiet and follows its /proc/<pid>/io
+        /// counters instead — immune to localized or reformatted dd output
+        #[arg(long = "dd-status", value_name = "MODE", default_value = "progress", value_parser = parse_dd_status)]
+        dd_status_none: bool,
+
+        /// Warn when the average write speed is below RATE per second — the stick
+        /// may be fake or worn out ("0" = never warn)
+        #[arg(long, value_name = "RATE", default_value = "4M", value_parser = parse_size)]
+        slow_threshold: u64,
+
+        /// Cap the write at RATE per second (e.g. "10M") so a shared disk or
+        /// hub isn't saturated — paced through pv ("0" = no cap)
+        #[arg(long, value_name = "RATE", value_parser = parse_size)]
+        rate_limit: Option<u64>,
+
+        /// Read up to SIZE of the image ahead of dd on a separate thread, so a
+        /// slow source and a slow drive overlap ("0" = let dd read it)
+        #[arg(long, value_name = "SIZE", default_value = "64M", value_parser = parse_size)]
+        read_buffer: u64,
+
+        /// Show a desktop notification when the write finishes (needs notify-send)
+        #[arg(long)]
+        notify: bool,
+
+        /// Block suspend/idle sleep until the write (and verify) is done — takes a
+        /// systemd-logind inhibitor lock through systemd-inhibit
+        #[arg(long)]
+        keep_awake: bool,
+
+        /// Final gate: the operator must type this exact phrase (e.g. an asset tag
+        /// or ERASE) before the write starts. Not skipped by --force.
+        #[arg(long, value_name = "PHRASE")]
+        confirm_string: Option<String>,
+
+        /// Write even though the device belongs to an md RAID array or LVM volume
+        /// group (--force does not cover this)
+        #[arg(long = "i-know-its-raid")]
+        allow_raid: bool,
+
+        /// Expert: treat the device as SIZE (e.g. 16G or 15931539456) in the fit check
+        /// and confirmation, for bridges that misreport it. You answer for it being right.
+        #[arg(
+            long,
+            value_name = "SIZE",
+            value_parser = parse_size,
+            conflicts_with_all = ["target_file", "mark", "check_mark", "extra_partition"]
+        )]
+        assume_device_size: Option<u64>,
+
+        /// Run this shell command after a fully successful write (and verify, if asked).
+        /// Gets BURN_DEVICE, BURN_ISO, BURN_BYTES and BURN_VERIFY in its environment;
+        /// a non-zero exit becomes burn's exit code. Never runs on failure or --dry-run.
+        #[arg(long, value_name = "CMD")]
+        after_command: Option<String>,
+
+        /// Partial write: start at this byte offset in both the image and the device (e.g. 1M)
+        #[arg(long, value_parser = parse_size,
+              conflicts_with_all = ["verify", "verify_streaming", "verify_bytes", "skip_if_verified", "checkpoint",
+                                    "write_checksum", "diagnose_mismatch", "mark", "check_mark", "extra_partition"])]
+        offset: Option<u64>,
+
+        /// Partial write: only write this many bytes (default: to the end of the image)
+        #[arg(long, value_parser = parse_size,
+              conflicts_with_all = ["verify", "verify_streaming", "verify_bytes", "skip_if_verified", "checkpoint",
+                                    "write_checksum", "diagnose_mismatch", "mark", "check_mark", "extra_partition"])]
+        size: Option<u64>,
+
+        /// With --dry-run: save the planned operations to a file (plan.txt, or plan.json for JSON)
+        #[arg(long, value_name = "PATH")]
+        output_image: Option<PathBuf>,
+
+        /// Abort if the write makes no progress for this many seconds (stalled hardware)
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<u64>,
+
+        /// After writing, read back the first sectors and check for an MBR/GPT boot signature
+        #[arg(long)]
+        check_boot: bool,
+
+        /// Testing only: write to a regular file instead of a USB device
+        #[arg(long, hide = true, value_name = "PATH", conflicts_with = "device")]
+        target_file: Option<PathBuf>,
+
+        /// Wait for a USB drive to be plugged in and use it as the target
+        #[arg(long, conflicts_with_all = ["device", "target_file"])]
+        watch: bool,
+    },
+
+    /// 🔍 Verify a previously written USB drive against an image, without writing
+    Verify {
+        /// Image the USB should hold (.iso, .img or a compressed one)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// USB device: /dev/sdb, model:<text> or serial:<text> — auto-detected if omitted
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// Compare against this image file instead of a USB device — both are hashed
+        /// and, if they differ, the first differing byte is reported
+        #[arg(long, value_name = "IMAGE", conflicts_with_all = ["device", "checkpoint", "write_checksum"])]
+        against: Option<PathBuf>,
+
+        /// Checksum used for the comparison: md5 or sha256
+        #[arg(long, value_name = "ALGO", default_value = "md5", value_parser = parse_hash)]
+        hash: HashKind,
+
+        /// Log per-256 MiB region hashes of image and USB to FILE as JSON lines
+        #[arg(long, value_name = "FILE")]
+        checkpoint: Option<PathBuf>,
+
+        /// Only compare the first SIZE of image and USB (default: the whole image)
+        #[arg(long, value_name = "SIZE", value_parser = parse_verify_bytes)]
+        verify_bytes: Option<u64>,
+
+        /// After a passing verify, save the image hash to FILE as "<hash>  <name>",
+        /// checkable with md5sum -c / sha256sum -c (appends with --force)
+        #[arg(long, value_name = "FILE")]
+        write_checksum: Option<PathBuf>,
+
+        /// On a mismatch, compare byte by byte to report the first differing
+        /// offset with a hexdump of both sides
+        #[arg(long)]
+        diagnose_mismatch: bool,
+
+        /// Block suspend/idle sleep until the verify is done (systemd-inhibit)
+        #[arg(long)]
+        keep_awake: bool,
+    },
+
+    /// 📋 List removable USB drives only
+    List {
+        /// Diagnostic: show every block device the kernel sees, marking unsafe ones
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// 🩺 Explain why each block device is or isn't offered as a USB target
+    Doctor,
+
+    /// 🎛️  Interactive wizard
+    Wizard,
+
+    /// 📊 Show device info
+    Info {
+        /// USB device: /dev/sdb, model:<text> or serial:<text>
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// Print the details, boot verdict and SMART values as one JSON object on
+        /// stdout; warnings and status lines go to stderr
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// 💽 Clone a USB drive to an image file (.gz / .zst compress)
+    Clone {
+        /// Source USB device: /dev/sdb, model:<text> or serial:<text> — auto-detected if omitted
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// Output image path (.img, .img.gz, .img.zst)
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// ⏏️  Unmount every partition of a USB drive, without writing anything
+    Unmount {
+        /// USB device: /dev/sdb, model:<text> or serial:<text> — auto-detected if omitted
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+
+    /// 🧹 Wipe a USB drive (zeros, random data, or just the partition table)
+    Wipe {
+        /// USB device: /dev/sdb, model:<text> or serial:<text> — auto-detected if omitted
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// zero = overwrite with zeros, random = overwrite with /dev/urandom,
+        /// quick = only the first and last 16 MiB (partition tables + filesystem headers)
+        #[arg(short, long, value_enum, default_value = "zero")]
+        mode: WipeMode,
+
+        /// Wipe even though the device belongs to an md RAID array or LVM volume group
+        #[arg(long = "i-know-its-raid")]
+        allow_raid: bool,
+    },
+
+    /// 🐚 Print a shell completion script to stdout
+    ///
+    /// e.g. burn completions bash > /etc/bash_completion.d/burn
+    ///      burn completions zsh > "${fpath[1]}/_burn"
+    ///      burn completions fish > ~/.config/fish/completions/burn.fish
+    Completions {
+        /// bash, zsh, fish, powershell or elvish
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum WipeMode {
+    Zero,
+    Random,
+    Quick,
+}
+
+// ─────────────────────────────────────────────
+//  BLOCK SIZE
+// ─────────────────────────────────────────────
+
+const MIN_BLOCK_SIZE: u64 = 512;
+const MAX_BLOCK_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Parse "4M" / "512K" / "1G" / "65536" → bytes (binary suffixes, like dd)
+fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let upper = t.to_ascii_uppercase();
+    let digits = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (num, mult) = match digits.chars().last() {
+        Some('K') => (&digits[..digits.len() - 1], 1024),
+        Some('M') => (&digits[..digits.len() - 1], 1024 * 1024),
+        Some('G') => (&digits[..digits.len() - 1], 1024 * 1024 * 1024),
+        Some('T') => (&digits[..digits.len() - 1], 1024 * 1024 * 1024 * 1024),
+        _ => (digits, 1),
+    };
+
+    let n: u64 = num
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid size (try 512K, 4M, 8G)", t))?;
+    n.checked_mul(mult)
+        .ok_or_else(|| format!("size '{}' is too large", t))
+}
+
+/// dd status= mode; true for "none"
+fn parse_dd_status(s: &str) -> Result<bool, String> {
+    match s {
+        "progress" => Ok(false),
+        "none" => Ok(true),
+        _ => Err(format!("'{}' is not a dd status mode (progress or none)", s)),
+    }
+}
+
+/// `--verify-bytes`: a size, but verifying nothing would be a pass that means nothing
+fn parse_verify_bytes(s: &str) -> Result<u64, String> {
+    match parse_size(s)? {
+        0 => Err("--verify-bytes must be larger than 0".into()),
+        n => Ok(n),
+    }
+}
+
+fn parse_hash(s: &str) -> Result<HashKind, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "md5" => Ok(HashKind::Md5),
+        "sha256" | "sha-256" => Ok(HashKind::Sha256),
+        _ => Err(format!("'{}' is not a supported hash (md5 or sha256)", s)),
+    }
+}
+
+fn parse_units(s: &str) -> Result<Units, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "si" => Ok(Units::Si),
+        "iec" => Ok(Units::Iec),
+        _ => Err(format!("'{}' is not a unit system (si or iec)", s)),
+    }
+}
+
+/// Parse and validate a dd block size
+fn parse_block_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let bytes = parse_size(t)?;
+
+    if bytes == 0 {
+        return Err("block size must be greater than zero".into());
+    }
+    if !bytes.is_power_of_two() {
+        return Err(format!(
+            "block size '{}' ({} bytes) is not a power of two — try {} or {}",
+            t,
+            bytes,
+            format_block_size(bytes.next_power_of_two() / 2),
+            format_block_size(bytes.next_power_of_two())
+        ));
+    }
+    if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&bytes) {
+        return Err(format!(
+            "block size '{}' is out of range ({} – {})",
+            t,
+            format_block_size(MIN_BLOCK_SIZE),
+            format_block_size(MAX_BLOCK_SIZE)
+        ));
+    }
+    Ok(bytes)
+}
+
+const DD_CONV_FLAGS: [&str; 3] = ["notrunc", "fsync", "fdatasync"];
+const DD_OFLAG_FLAGS: [&str; 4] = ["sync", "dsync", "direct", "nocache"];
+
+/// Check a comma-separated dd flag list against an allowlist
+fn parse_dd_flags(s: &str, what: &str, allowed: &[&str]) -> Result<String, String> {
+    let flags: Vec<&str> = s.split(',').map(str::trim).filter(|f| !f.is_empty()).collect();
+    if flags.is_empty() {
+        return Err(format!("empty {} list", what));
+    }
+    if let Some(bad) = flags.iter().find(|f| !allowed.contains(f)) {
+        return Err(format!(
+            "unsupported {} flag '{}' — allowed: {}",
+            what, bad, allowed.join(", ")
+        ));
+    }
+    Ok(flags.join(","))
+}
+
+fn parse_dd_conv(s: &str) -> Result<String, String> {
+    parse_dd_flags(s, "--dd-conv", &DD_CONV_FLAGS)
+}
+
+/// Like `parse_dd_conv`; "none" turns the default oflag=sync off
+fn parse_dd_oflag(s: &str) -> Result<String, String> {
+    if s.trim() == "none" {
+        return Ok("none".into());
+    }
+    parse_dd_flags(s, "--dd-oflag", &DD_OFLAG_FLAGS)
+}
+
+// ─────────────────────────────────────────────
+//  CONFIG FILE
+// ─────────────────────────────────────────────
+
+/// Drives above this size get an extra typed confirmation (decimal bytes)
+const DEFAULT_LARGE_DEVICE_BYTES: u64 = 128_000_000_000;
+
+/// Settings from `~/.config/burn/config.toml` (simple `key = value` lines),
+/// plus the machine policy from [`SYSTEM_CONFIG`]
+#[derive(Debug, Clone)]
+struct Config {
+    large_device_bytes: u64,
+    /// `allowed_devices = ["usb-Front_Port_…", "SERIAL123"]` in [`SYSTEM_CONFIG`]:
+    /// the only drives burn will ever offer or accept (by-id links or serials).
+    /// Kiosk policy — an empty or absent list means no restriction.
+    allowed_devices: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { large_device_bytes: DEFAULT_LARGE_DEVICE_BYTES, allowed_devices: Vec::new() }
+    }
+}
+
+/// Machine-wide policy, read whoever runs burn (sudo and pkexec reset HOME,
+/// so a per-user file would silently drop it). It must be owned by root and
+/// writable by nobody else — operators can't relax a policy they can edit.
+const SYSTEM_CONFIG: &str = "/etc/burn/config.toml";
+
+fn config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(base.join("burn").join("config.toml"))
+}
+
+/// `key = value` lines of a config file → (line number, key, value)
+fn config_entries(path: &Path) -> Result<Vec<(usize, String, String)>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Cannot read config: {}", path.display()))?;
+    let mut entries = Vec::new();
+    for (n, raw) in text.lines().enumerate() {
+        let line = raw.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            anyhow!("{}:{}: expected `key = value`", path.display(), n + 1)
+        })?;
+        entries.push((n + 1, key.trim().to_string(), value.trim().trim_matches('"').to_string()));
+    }
+    Ok(entries)
+}
+
+impl Config {
+    /// Load the config files if there are any; missing files mean defaults
+    fn load() -> Result<Config> {
+        let mut cfg = Config::default();
+        cfg.load_policy()?;
+        let path = match config_path() {
+            Some(p) if p.exists() => p,
+            _ => return Ok(cfg),
+        };
+
+        for (n, key, value) in config_entries(&path)? {
+            match key.as_str() {
+                "large_device_gb" => {
+                    let gb: u64 = value.parse().map_err(|_| {
+                        anyhow!("{}:{}: large_device_gb must be a whole number", path.display(), n)
+                    })?;
+                    cfg.large_device_bytes = gb * 1_000_000_000;
+                }
+                "allowed_devices" => warn(&format!(
+                    "{}:{}: allowed_devices is only read from {} — ignored",
+                    path.display(), n, SYSTEM_CONFIG
+                )),
+                other => warn(&format!(
+                    "{}:{}: unknown config key '{}' — ignored",
+                    path.display(), n, other
+                )),
+            }
+        }
+        log::debug!("Loaded config from {}: {:?}", path.display(), cfg);
+        Ok(cfg)
+    }
+
+    /// [`SYSTEM_CONFIG`], if present. A file anyone but root could have
+    /// changed is refused outright rather than half-trusted.
+    fn load_policy(&mut self) -> Result<()> {
+        use std::os::unix::fs::MetadataExt;
+        let path = Path::new(SYSTEM_CONFIG);
+        let Ok(meta) = fs::metadata(path) else { return Ok(()) };
+        if meta.uid() != 0 || meta.mode() & 0o022 != 0 {
+            return Err(anyhow!(
+                "{} must be owned by root and not group/world-writable (chown root: {0}; chmod go-w {0})",
+                SYSTEM_CONFIG
+            ));
+        }
+        for (n, key, value) in config_entries(path)? {
+            match key.as_str() {
+                // A TOML-style array or a bare comma-separated list
+                "allowed_devices" => {
+                    self.allowed_devices = value
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .map(|e| e.trim().trim_matches('"').to_string())
+                        .filter(|e| !e.is_empty())
+                        .collect();
+                }
+                other => warn(&format!(
+                    "{}:{}: unknown policy key '{}' — ignored",
+                    SYSTEM_CONFIG, n, other
+                )),
+            }
+        }
+        log::debug!("Loaded policy from {}: allowed_devices {:?}", SYSTEM_CONFIG, self.allowed_devices);
+        Ok(())
+    }
+}
+
+// ─────────────────────────────────────────────
+//  WRITE OPTIONS
+// ─────────────────────────────────────────────
+
+/// Size of the optional data partition added after the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExtraPartition {
+    /// Use all remaining space on the device
+    Rest,
+    /// Fixed size in bytes
+    Size(u64),
+}
+
+impl ExtraPartition {
+    fn describe(&self) -> String {
+        match self {
+            ExtraPartition::Rest => "all remaining space".to_string(),
+            ExtraPartition::Size(b) => format_size(*b),
+        }
+    }
+}
+
+fn parse_extra_partition(s: &str) -> Result<ExtraPartition, String> {
+    if s.trim().eq_ignore_ascii_case("rest") {
+        return Ok(ExtraPartition::Rest);
+    }
+    match parse_size(s)? {
+        0 => Err("extra partition size must be greater than zero".into()),
+        b => Ok(ExtraPartition::Size(b)),
+    }
+}
+
+/// Longest volume label FAT32 (and exFAT) accept
+const MAX_LABEL_LEN: usize = 11;
+/// Characters FAT forbids in a label (exFAT forbids a subset of these)
+const LABEL_FORBIDDEN: &str = "\"*+,./:;<=>?[\\]|";
+
+/// `--label`: checked against the FAT rules, the stricter of the two
+/// filesystems the data partition may get, so it's valid for either
+fn parse_label(s: &str) -> Result<String, String> {
+    let label = s.trim();
+    if label.is_empty() {
+        return Err("the label is empty".into());
+    }
+    if label.chars().count() > MAX_LABEL_LEN {
+        return Err(format!(
+            "'{}' is {} characters — FAT/exFAT labels are at most {}",
+            label, label.chars().count(), MAX_LABEL_LEN
+        ));
+    }
+    if let Some(c) = label.chars().find(|c| !c.is_ascii() || c.is_ascii_control()) {
+        return Err(format!(
+            "'{}' contains {:?} — use plain ASCII letters, digits, spaces, - and _",
+            label, c
+        ));
+    }
+    if let Some(c) = label.chars().find(|c| LABEL_FORBIDDEN.contains(*c)) {
+        return Err(format!(
+            "'{}' contains '{}' — FAT labels can't use any of {}",
+            label, c, LABEL_FORBIDDEN
+        ));
+    }
+    Ok(label.to_string())
+}
+
+/// Extra readback attempts before a verify counts as a read failure
+const DEFAULT_VERIFY_RETRIES: u32 = 2;
+
+/// Block size for the second attempt after an I/O error — some flaky
+/// controllers choke on 4M requests but cope with 1M
+const RETRY_BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Average write speed (bytes/s) below which the stick is flagged as suspicious
+const DEFAULT_SLOW_THRESHOLD: u64 = 4 * 1024 * 1024;
+
+/// Writes shorter than this are too short for a meaningful speed reading
+const SLOW_CHECK_MIN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// `verify --against`: per-region hashes this size narrow down where two images
+/// differ, so only one region has to be compared byte by byte
+const COMPARE_CHUNK: u64 = 16 * 1024 * 1024;
+
+/// Everything `do_write` needs besides the source and target
+#[derive(Debug, Clone)]
+struct WriteOptions {
+    verify: bool,
+    /// Check the image against a sibling checksum file before writing
+    source_verify: bool,
+    /// Read the whole image once before writing (source-side I/O errors)
+    precheck_source: bool,
+    verify_streaming: bool,
+    /// Extra attempts when the readback dd fails (transient I/O errors)
+    verify_retries: u32,
+    /// Checksum compared by verify
+    hash: HashKind,
+    /// Per-region hashes from verify, as JSON lines
+    checkpoint: Option<PathBuf>,
+    /// Save the verified image hash here, md5sum/sha256sum -c style
+    write_checksum: Option<PathBuf>,
+    /// Only verify this much of the image (None = all of it)
+    verify_bytes: Option<u64>,
+    /// After a mismatch, find and show the first differing byte
+    diagnose_mismatch: bool,
+    block_size: u64,
+    /// Retry once at RETRY_BLOCK_SIZE after an I/O error
+    retry: bool,
+    extra_partition: Option<ExtraPartition>,
+    /// Volume label for the data partition
+    label: Option<String>,
+    eject: bool,
+    skip_if_verified: bool,
+    /// Record / check the image fingerprint in the last sector
+    mark: bool,
+    check_mark: bool,
+    /// Mark as protected reference media and set the kernel ro flag
+    protect: bool,
+    /// Zero both ends of the device before the write
+    zap: bool,
+    /// blkdiscard the whole device before the write
+    discard: bool,
+    check_boot: bool,
+    /// Abort when the write stalls this long
+    timeout: Option<Duration>,
+    /// Periodic fdatasync while dd runs (0 = off)
+    sync_interval: u64,
+    /// dd status=none, progress from /proc/<pid>/io
+    dd_status_none: bool,
+    /// Warn below this average write speed in bytes/s (0 = off)
+    slow_threshold: u64,
+    /// Write speed cap in bytes/s (pv -L)
+    rate_limit: Option<u64>,
+    /// Image bytes read ahead of dd (0 = off)
+    read_buffer: u64,
+    /// Only write part of the image
+    range: Option<WriteRange>,
+    /// Desktop notification when done
+    notify: bool,
+    /// Hold a logind sleep inhibitor while writing/verifying
+    keep_awake: bool,
+    /// Shell command run after a successful write
+    after_command: Option<String>,
+    /// Dry-run: also save the plan here (.json → JSON, otherwise text)
+    plan_path: Option<PathBuf>,
+    /// Validated dd conv= / oflag= values (None = not passed)
+    dd_conv: Option<String>,
+    dd_oflag: Option<String>,
+    /// --direct: oflag=direct, falling back to sync if it's refused
+    direct: bool,
+    /// Drives larger than this need the device name typed to confirm
+    large_device_bytes: u64,
+    /// Phrase that must be typed before writing (policy — survives --force)
+    confirm_string: Option<String>,
+    /// Overwrite a RAID/LVM member (--i-know-its-raid)
+    allow_raid: bool,
+    force: bool,
+    dry_run: bool,
+    verbose: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            verify: false,
+            source_verify: true,
+            precheck_source: false,
+            verify_streaming: false,
+            verify_retries: DEFAULT_VERIFY_RETRIES,
+            hash: HashKind::Md5,
+            checkpoint: None,
+            write_checksum: None,
+            verify_bytes: None,
+            diagnose_mismatch: false,
+            block_size: DEFAULT_BLOCK_SIZE,
+            retry: true,
+            extra_partition: None,
+            label: None,
+            eject: false,
+            skip_if_verified: false,
+            mark: false,
+            check_mark: false,
+            protect: false,
+            zap: false,
+            discard: false,
+            check_boot: false,
+            timeout: None,
+            sync_interval: DEFAULT_SYNC_INTERVAL,
+            dd_status_none: false,
+            slow_threshold: DEFAULT_SLOW_THRESHOLD,
+            rate_limit: None,
+            read_buffer: 0,
+            range: None,
+            notify: false,
+            keep_awake: false,
+            after_command: None,
+            confirm_string: None,
+            allow_raid: false,
+            plan_path: None,
+            dd_conv: None,
+            dd_oflag: Some("sync".to_string()),
+            direct: false,
+            large_device_bytes: DEFAULT_LARGE_DEVICE_BYTES,
+            force: false,
+            dry_run: false,
+            verbose: false,
+        }
+    }
+}
+
+// ─────────────────────────────────────────────
+//  DETECT USB DRIVES (SAFE)
+// ─────────────────────────────────────────────
+
+/// Default target when `--device` is omitted (single-slot machines)
+const DEFAULT_DEVICE_ENV: &str = "BURN_DEFAULT_DEVICE";
+
+/// `--device`, else `$BURN_DEFAULT_DEVICE`, else the picker. The variable is
+/// only a default: if it doesn't name a detected USB drive right now, the
+/// picker is shown instead of failing.
+fn resolve_device(scanner: &DeviceScanner, arg: Option<String>, min_size: Option<u64>) -> Result<UsbDevice> {
+    if let Some(d) = arg {
+        return find_device(scanner, &d);
+    }
+    let Some(d) = std::env::var(DEFAULT_DEVICE_ENV).ok().filter(|d| !d.trim().is_empty()) else {
+        return select_usb_device(scanner, min_size);
+    };
+    match find_device(scanner, d.trim()) {
+        Ok(device) => {
+            info(&format!("Using {} from {}", device.path.bright_cyan(), DEFAULT_DEVICE_ENV));
+            Ok(device)
+        }
+        Err(e) => {
+            warn(&format!("{}={} is not usable right now ({:#}) — choose a drive instead", DEFAULT_DEVICE_ENV, d, e));
+            select_usb_device(scanner, min_size)
+        }
+    }
+}
+
+/// `--assume-device-size`: replace the size the kernel reports, loudly
+fn assume_size(mut device: UsbDevice, size: u64) -> Result<UsbDevice> {
+    if size == 0 {
+        return Err(anyhow!("--assume-device-size must be larger than 0"));
+    }
+    warn(&format!(
+        "--assume-device-size: treating {} as {} ({} bytes) — the kernel reports {} ({} bytes)",
+        device.path, format_size(size), size, device.size_human(), device.reported_size
+    ));
+    warn("  Fit checks now trust YOUR number. If it's wrong, the write can run past the end of the stick.");
+    log::warn!(
+        "--assume-device-size: {} overridden from {} to {} bytes",
+        device.path, device.reported_size, size
+    );
+    device.size = size;
+    Ok(device)
+}
+
+/// Resolve `--device`: a /dev path (a /dev/disk/by-id/… link works too),
+/// `model:<substr>` or `serial:<substr>` (case-insensitive). Must match
+/// exactly one detected USB drive.
+fn find_device(scanner: &DeviceScanner, arg: &str) -> Result<UsbDevice> {
+    let devices = scanner.devices().iter().cloned();
+
+    let (what, needle) = match arg.split_once(':') {
+        Some((k @ ("model" | "serial"), v)) => (k, v),
+        _ => {
+            // A by-id (or any other) symlink → the /dev/sdX behind it
+            let node = fs::canonicalize(arg).ok().map(|p| p.display().to_string());
+            return devices.into_iter()
+                .find(|dev| dev.path == arg || node.as_deref() == Some(dev.path.as_str()))
+                .ok_or_else(|| fail(EXIT_NO_DEVICE, anyhow!(
+                    "'{}' is not a detected USB drive{}.\n\
+                     Use 'burn list' to see available USB devices.",
+                    arg, allow_list_note()
+                )));
+        }
+    };
+
+    let lower = needle.to_lowercase();
+    let matches: Vec<UsbDevice> = devices.into_iter().filter(|d| {
+        let field = if what == "model" { Some(&d.model) } else { d.serial.as_ref() };
+        field.is_some_and(|f| f.to_lowercase().contains(&lower))
+    }).collect();
+
+    match matches.len() {
+        1 => Ok(matches.into_iter().next().unwrap()),
+        0 => Err(fail(EXIT_NO_DEVICE, anyhow!(
+            "No USB drive with {} matching '{}'{}.\n\
+             Use 'burn list' to see available USB devices.",
+            what, needle, allow_list_note()
+        ))),
+        n => Err(anyhow!(
+            "{} USB drives have a {} matching '{}': {} — be more specific",
+            n, what, needle,
+            matches.iter().map(|d| d.path.as_str()).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// With an allow-list, a drive that's plugged in can still be "not found"
+fn allow_list_note() -> &'static str {
+    if allowed_devices().is_empty() { "" } else { " among the allowed_devices in /etc/burn/config.toml" }
+}
+
+// ─────────────────────────────────────────────
+//  EXTERNAL TOOLS
+// ─────────────────────────────────────────────
+
+/// An external binary a subcommand relies on
+struct ToolReq {
+    name: &'static str,
+    /// Missing required tools abort; optional ones only warn
+    required: bool,
+}
+
+const fn required(name: &'static str) -> ToolReq {
+    ToolReq { name, required: true }
+}
+
+const fn optional(name: &'static str) -> ToolReq {
+    ToolReq { name, required: false }
+}
+
+/// Distro package providing a tool (same name on apt/dnf/pacman)
+fn tool_package(tool: &str) -> &'static str {
+    match tool {
+        "dd" | "sync" | "head" => "coreutils",
+        "umount" | "lsblk" => "util-linux",
+        "udisksctl" => "udisks2",
+        "parted" | "partprobe" => "parted",
+        "mkfs.exfat" => "exfatprogs",
+        "mkfs.vfat" => "dosfstools",
+        "gzip" => "gzip",
+        "xz" => "xz-utils",
+        "zstd" => "zstd",
+        _ => "",
+    }
+}
+
+/// Install command for the running distro, from /etc/os-release
+fn install_command(packages: &[&str]) -> String {
+    let os_release = fs::read_to_string("/etc/os-release").unwrap_or_default();
+    let ids: String = os_release
+        .lines()
+        .filter(|l| l.starts_with("ID=") || l.starts_with("ID_LIKE="))
+        .map(|l| l.split_once('=').map(|(_, v)| v.trim_matches('"')).unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let has = |id: &str| ids.split_whitespace().any(|i| i == id);
+
+    let prefix = if has("debian") || has("ubuntu") {
+        "sudo apt install"
+    } else if has("fedora") || has("rhel") || has("centos") {
+        "sudo dnf install"
+    } else if has("arch") {
+        "sudo pacman -S"
+    } else if has("suse") || has("opensuse") {
+        "sudo zypper install"
+    } else {
+        "install with your package manager:"
+    };
+    format!("{} {}", prefix, packages.join(" "))
+}
+
+/// External tools each subcommand needs
+fn tools_for(command: &Commands) -> Vec<ToolReq> {
+    match command {
+        Commands::Write {
+            image_pos, input, extra_partition, label, eject, rate_limit, keep_awake, discard, protect, ..
+        } => {
+            let input = input.as_deref().or(image_pos.as_deref());
+            let mut t = vec![required("dd"), required("sync"), required("umount"), optional("lsblk")];
+            if let Some(tool) = input.and_then(compression_tool) {
+                t.push(required(tool));
+            }
+            if input.is_some_and(is_url) {
+                t.push(required("curl"));
+            }
+            if extra_partition.is_some() {
+                t.extend([required("parted"), optional("partprobe"), optional("mkfs.exfat"), optional("mkfs.vfat")]);
+            }
+            if label.is_some() {
+                t.push(optional("exfatlabel"));
+            }
+            if *eject {
+                t.push(optional("udisksctl"));
+            }
+            if *keep_awake {
+                t.push(optional("systemd-inhibit"));
+            }
+            if rate_limit.is_some() {
+                t.push(optional("pv"));
+            }
+            if *discard {
+                t.push(optional("blkdiscard"));
+            }
+            if *protect {
+                t.push(optional("blockdev"));
+            }
+            t
+        }
+        Commands::Clone { output, .. } => {
+            let mut t = vec![required("dd"), required("sync")];
+            if let Some(c) = compression_tool(output) {
+                t.push(required(c));
+            }
+            t
+        }
+        Commands::Wizard => vec![
+            required("dd"), required("sync"), required("umount"), optional("lsblk"),
+        ],
+        Commands::Verify { input, against, keep_awake, .. } => {
+            let mut t = vec![];
+            for image in [input, against] {
+                if let Some(tool) = image.as_deref().and_then(compression_tool) {
+                    t.push(required(tool));
+                }
+            }
+            if *keep_awake {
+                t.push(optional("systemd-inhibit"));
+            }
+            t
+        }
+        Commands::Info { .. } | Commands::Doctor => vec![optional("lsblk")],
+        Commands::Wipe { .. } => vec![required("dd"), required("sync"), required("umount"), optional("lsblk")],
+        Commands::Unmount { .. } => vec![required("umount")],
+        Commands::List { .. } | Commands::Completions { .. } => vec![],
+    }
+}
+
+/// Check that the external tools a subcommand needs are installed
+fn preflight_tools(tools: &[ToolReq]) -> Result<()> {
+    let mut missing_required = Vec::new();
+    let mut missing_optional = Vec::new();
+
+    for tool in tools {
+        let found = if tool.name == "dd" {
+            match dd_path_override() {
+                Some(p) => p.is_file(),
+                None => find_tool("dd").is_some(),
+            }
+        } else {
+            find_tool(tool.name).is_some()
+        };
+        if !found {
+            if tool.required {
+                missing_required.push(tool.name);
+            } else {
+                missing_optional.push(tool.name);
+            }
+        }
+    }
+
+    let packages = |names: &[&str]| {
+        let mut pkgs: Vec<&str> = names.iter().map(|n| tool_package(n)).filter(|p| !p.is_empty()).collect();
+        pkgs.sort();
+        pkgs.dedup();
+        pkgs
+    };
+
+    if !missing_optional.is_empty() {
+        warn(&format!("Optional tools not found: {}", missing_optional.join(", ")));
+        let pkgs = packages(&missing_optional);
+        if !pkgs.is_empty() {
+            info(&format!("To enable them: {}", install_command(&pkgs)));
+        }
+    }
+
+    if !missing_required.is_empty() {
+        let pkgs = packages(&missing_required);
+        if missing_required.contains(&"dd") {
+            if let Some(p) = dd_path_override() {
+                err_msg(&format!("--dd-path {} is not a file", p.display()));
+            }
+        }
+        return Err(anyhow!(
+            "Missing required tools: {}\n\
+             Install them with:\n  {}",
+            missing_required.join(", "),
+            install_command(&pkgs)
+        ));
+    }
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+//  EXIT CODES
+// ─────────────────────────────────────────────
+
+/// Exit codes scripts can branch on — keep `burn --help` in sync.
+/// Anything else that goes wrong exits with 1.
+const EXIT_CANCELLED: i32 = 2;
+const EXIT_NO_DEVICE: i32 = 3;
+const EXIT_TOO_SMALL: i32 = 4;
+const EXIT_WRITE_FAILED: i32 = 5;
+const EXIT_VERIFY_FAILED: i32 = 6;
+/// Root is needed but we can't ask (non-interactive or --force)
+const EXIT_NEEDS_ROOT: i32 = 7;
+
+/// An error that decides the exit code; displays as the error it wraps
+#[derive(Debug)]
+struct Exit {
+    code: i32,
+    error: anyhow::Error,
+}
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for Exit {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Tag an error with the exit code `main` should use for it
+fn fail(code: i32, error: anyhow::Error) -> anyhow::Error {
+    anyhow::Error::new(Exit { code, error })
+}
+
+/// The user said no at a prompt — `main` exits with EXIT_CANCELLED, silently
+fn cancelled() -> anyhow::Error {
+    BurnError::Cancelled.into()
+}
+
+fn exit_code(error: &anyhow::Error) -> i32 {
+    if let Some(e) = error.chain().find_map(|e| e.downcast_ref::<Exit>()) {
+        return e.code;
+    }
+    // The first named kind anywhere in the chain, past the library's context
+    let kind = error
+        .chain()
+        .filter_map(|e| e.downcast_ref::<BurnError>())
+        .map(BurnError::kind)
+        .find(|e| !matches!(e, BurnError::Context { .. } | BurnError::Other(_)));
+    match kind {
+        Some(BurnError::NoDevicesFound) => EXIT_NO_DEVICE,
+        Some(BurnError::DeviceTooSmall { .. }) => EXIT_TOO_SMALL,
+        Some(BurnError::WriteFailed { .. }) => EXIT_WRITE_FAILED,
+        Some(BurnError::VerifyMismatch { .. }) => EXIT_VERIFY_FAILED,
+        Some(BurnError::PermissionDenied { .. }) => EXIT_NEEDS_ROOT,
+        Some(BurnError::Cancelled) => EXIT_CANCELLED,
+        Some(BurnError::Context { .. } | BurnError::Other(_)) | None => 1,
+    }
+}
+
+// ─────────────────────────────────────────────
+//  PRIVILEGES
+// ─────────────────────────────────────────────
+
+/// Effective uid from /proc/self/status (None if it can't be read)
+fn effective_uid() -> Option<u32> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status.lines()
+        .find_map(|l| l.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+/// Subcommands that open a raw block device
+fn needs_root(command: &Commands, dry_run: bool) -> bool {
+    match command {
+        Commands::Write { target_file, .. } => !dry_run && target_file.is_none(),
+        Commands::Wipe { .. } | Commands::Clone { .. } | Commands::Unmount { .. } => !dry_run,
+        // Only reads, but the raw device is root-only all the same
+        Commands::Verify { against, .. } => !dry_run && against.is_none(),
+        _ => false,
+    }
+}
+
+/// Not root: offer to re-run the exact command line through sudo (or pkexec).
+/// With --force or no terminal, print the command and fail with EXIT_NEEDS_ROOT.
+fn ensure_root(command: &Commands, force: bool, dry_run: bool) -> Result<()> {
+    use std::io::IsTerminal;
+    if !needs_root(command, dry_run) || effective_uid().is_none_or(|uid| uid == 0) {
+        return Ok(());
+    }
+
+    let elevator = ["sudo", "pkexec"].into_iter().find(|t| find_tool(t).is_some());
+    let exe = std::env::current_exe().context("Cannot find the burn executable")?;
+    let args: Vec<std::ffi::OsString> = std::env::args_os().skip(1).collect();
+    let cmdline = std::iter::once(elevator.unwrap_or("sudo").to_string())
+        .chain(std::iter::once(exe.display().to_string()))
+        .chain(args.iter().map(|a| a.to_string_lossy().to_string()))
+        .map(|a| shell_quote(&a))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    warn("Writing to a USB device needs root privileges.");
+    let ask = !force && std::io::stdin().is_terminal();
+    let rerun = match elevator {
+        Some(tool) if ask => Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Re-run this command with {}?", tool))
+            .default(true)
+            .interact()?,
+        _ => false,
+    };
+    if !rerun {
+        info(&format!("Run: {}", cmdline));
+        return Err(fail(EXIT_NEEDS_ROOT, anyhow!("Not running as root")));
+    }
+
+    use std::os::unix::process::CommandExt;
+    let tool = elevator.unwrap_or("sudo");
+    log::info!("Re-executing via {}: {}", tool, cmdline);
+    let err = Command::new(tool).arg(&exe).args(&args).exec();
+    Err(anyhow!("Failed to run {}: {}", tool, err))
+}
+
+// ─────────────────────────────────────────────
+//  LOGGING
+// ─────────────────────────────────────────────
+
+/// Route `log` records to a plain-text file (console output is unaffected)
+fn init_log_file(path: &PathBuf) -> Result<()> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Cannot open log file: {}", path.display()))?;
+
+    env_logger::Builder::new()
+        .target(env_logger::Target::Pipe(Box::new(file)))
+        .filter_level(log::LevelFilter::Debug)
+        .write_style(env_logger::WriteStyle::Never)
+        .format_timestamp_millis()
+        .try_init()
+        .context("Failed to initialise logger")?;
+
+    log::info!(
+        "burn {} started: {}",
+        env!("CARGO_PKG_VERSION"),
+        std::env::args().collect::<Vec<_>>().join(" ")
+    );
+    Ok(())
+}
+
+/// Remove ANSI color escapes so log lines stay plain text
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            chars.next();
+            // Skip parameters until the final byte (@ … ~)
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) { break; }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// ─────────────────────────────────────────────
+//  MACHINE-READABLE PROGRESS
+// ─────────────────────────────────────────────
+
+/// `--progress-fd` sink, set once in `main`
+static PROGRESS_FD: OnceLock<Mutex<fs::File>> = OnceLock::new();
+
+fn init_progress_fd(fd: i32) -> Result<()> {
+    use std::os::fd::FromRawFd;
+    if fd < 0 || !Path::new(&format!("/proc/self/fd/{}", fd)).exists() {
+        return Err(anyhow!("--progress-fd {}: file descriptor is not open", fd));
+    }
+    // SAFETY: the fd is open and handed to us by the parent; it stays
+    // owned by the static for the rest of the run.
+    let file = unsafe { fs::File::from_raw_fd(fd) };
+    let _ = PROGRESS_FD.set(Mutex::new(file));
+    Ok(())
+}
+
+fn machine_progress() -> bool {
+    PROGRESS_FD.get().is_some()
+}
+
+/// One NDJSON event, e.g. {"phase":"write","bytes":123,"total":456,"rate_bps":789}
+fn progress_event(phase: &str, fields: &[(&str, Option<u64>)]) {
+    use std::io::Write;
+    let Some(sink) = PROGRESS_FD.get() else { return };
+    let mut line = format!("{{\"phase\":\"{}\"", phase);
+    for (key, value) in fields {
+        if let Some(v) = value {
+            line.push_str(&format!(",\"{}\":{}", key, v));
+        }
+    }
+    line.push_str("}\n");
+    if let Ok(mut f) = sink.lock() {
+        let _ = f.write_all(line.as_bytes());
+    }
+}
+
+// ─────────────────────────────────────────────
+//  RUN SUMMARY
+// ─────────────────────────────────────────────
+
+/// `--summary-json` destination, set once in `run`
+static SUMMARY_PATH: OnceLock<PathBuf> = OnceLock::new();
+/// Filled in as a write/verify goes along; `main` saves it however it ends
+static SUMMARY: Mutex<RunSummary> = Mutex::new(RunSummary::new());
+
+struct RunSummary {
+    /// "write" or "verify" — nothing is saved for other commands
+    command: Option<&'static str>,
+    started: Option<Instant>,
+    dry_run: bool,
+    image: Option<String>,
+    /// (algorithm, hash) of the image as read for the verify
+    image_hash: Option<(&'static str, String)>,
+    device: Option<String>,
+    model: Option<String>,
+    serial: Option<String>,
+    bytes_written: Option<u64>,
+    throughput: Option<u64>,
+    verified: Option<bool>,
+}
+
+impl RunSummary {
+    const fn new() -> Self {
+        RunSummary {
+            command: None,
+            started: None,
+            dry_run: false,
+            image: None,
+            image_hash: None,
+            device: None,
+            model: None,
+            serial: None,
+            bytes_written: None,
+            throughput: None,
+            verified: None,
+        }
+    }
+
+    fn target(&mut self, image: &Path, device: &UsbDevice) {
+        self.image = Some(image.display().to_string());
+        self.device = Some(device.path.clone());
+        self.model = Some(device.model.clone());
+        self.serial = device.serial.clone();
+    }
+}
+
+/// Record part of the summary (a no-op without --summary-json)
+fn summarize(f: impl FnOnce(&mut RunSummary)) {
+    if SUMMARY_PATH.get().is_none() {
+        return;
+    }
+    if let Ok(mut s) = SUMMARY.lock() {
+        f(&mut s);
+    }
+}
+
+/// Save the summary with the run's exit status; failures only warn, the
+/// run's own result is what counts
+fn write_summary(code: i32, error: Option<&anyhow::Error>) {
+    let Some(path) = SUMMARY_PATH.get() else { return };
+    let Ok(s) = SUMMARY.lock() else { return };
+    let Some(command) = s.command else { return };
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "null".into());
+    let json = format!(
+        "{{\"tool\":\"burn\",\"version\":{},\"timestamp\":{},\"command\":{},\"dry_run\":{},\"image\":{},\"hash_algorithm\":{},\"image_hash\":{},\"device\":{},\"model\":{},\"serial\":{},\"bytes_written\":{},\"duration_s\":{},\"throughput_bps\":{},\"verified\":{},\"exit_status\":{},\"error\":{}}}\n",
+        json_str(env!("CARGO_PKG_VERSION")),
+        unix_now(),
+        json_str(command),
+        s.dry_run,
+        opt(s.image.as_deref().map(json_str)),
+        opt(s.image_hash.as_ref().map(|(algo, _)| json_str(algo))),
+        opt(s.image_hash.as_ref().map(|(_, hash)| json_str(hash))),
+        opt(s.device.as_deref().map(json_str)),
+        opt(s.model.as_deref().map(json_str)),
+        opt(s.serial.as_deref().map(json_str)),
+        opt(s.bytes_written.map(|v| v.to_string())),
+        opt(s.started.map(|t| format!("{:.1}", t.elapsed().as_secs_f64()))),
+        opt(s.throughput.map(|v| v.to_string())),
+        opt(s.verified.map(|v| v.to_string())),
+        code,
+        opt(error.map(|e| json_str(&strip_ansi(&format!("{:#}", e))))),
+    );
+    match fs::write(path, json) {
+        Ok(()) => log::info!("summary saved to {}", path.display()),
+        Err(e) => warn(&format!("Cannot write --summary-json {}: {}", path.display(), e)),
+    }
+}
+
+/// Progress goes to the fd instead (or --quiet) — keep the terminal bars out of the way.
+/// In line mode the bar is hidden too and reported as plain lines.
+fn hide_if_machine(pb: &ProgressBar) {
+    track_bar(pb);
+    if machine_progress() || quiet() || line_progress().is_some() {
+        pb.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    }
+    if let Some(every) = line_progress() {
+        spawn_line_reporter(pb, every);
+    }
+}
+
+/// One MultiProgress per write/verify run: its bars share a draw target, and
+/// text printed through it lands above them instead of through a redraw
+fn phase_multi() -> MultiProgress {
+    let multi = MultiProgress::new();
+    if machine_progress() || quiet() || line_progress().is_some() {
+        multi.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    }
+    multi
+}
+
+/// `multi.add`, plus the line reporter in line mode
+fn add_bar(multi: &MultiProgress, pb: ProgressBar) -> ProgressBar {
+    let pb = multi.add(pb);
+    track_bar(&pb);
+    if let Some(every) = line_progress() {
+        spawn_line_reporter(&pb, every);
+    }
+    pb
+}
+
+/// Text above the live bars; in line mode there are none, so straight to stdout
+fn above(multi: &MultiProgress, line: impl AsRef<str>) {
+    if line_progress().is_some() {
+        println!("{}", line.as_ref());
+    } else {
+        let _ = multi.println(line);
+    }
+}
+
+/// Blank separator between phases, printed above any live bars
+fn gap(multi: &MultiProgress) {
+    above(multi, "");
+}
+
+// ─────────────────────────────────────────────
+//  LINE-MODE PROGRESS
+// ─────────────────────────────────────────────
+
+/// Set once in `main` when stdout isn't a terminal: bars are swapped for a
+/// newline-terminated status line this often, so redirected output stays readable
+static LINE_PROGRESS: OnceLock<Duration> = OnceLock::new();
+
+fn line_progress() -> Option<Duration> {
+    LINE_PROGRESS.get().copied()
+}
+
+/// Print the bar's state every `every` until it finishes (then its final
+/// message) or is dropped
+fn spawn_line_reporter(pb: &ProgressBar, every: Duration) {
+    let weak = pb.downgrade();
+    thread::spawn(move || {
+        let mut last = Instant::now();
+        loop {
+            thread::sleep(Duration::from_millis(100));
+            let Some(pb) = weak.upgrade() else { return };
+            if pb.is_finished() {
+                let msg = strip_ansi(&pb.message());
+                if !msg.is_empty() {
+                    println!("{}", msg);
+                }
+                return;
+            }
+            if last.elapsed() >= every {
+                last = Instant::now();
+                println!("{}", progress_line(&pb));
+            }
+        }
+    });
+}
+
+/// "25%  1.2 GB / 4.8 GB  120 MB/s  <message>"; without a length, bytes and rate only
+fn progress_line(pb: &ProgressBar) -> String {
+    let pos = pb.position();
+    let msg = strip_ansi(&pb.message());
+    let rate = format!("{}/s", format_size(pb.per_sec() as u64));
+    match pb.length().filter(|&n| n > 0) {
+        Some(total) => format!(
+            "{:>3}%  {} / {}  {}  {}",
+            pos.min(total) * 100 / total,
+            format_size(pos),
+            format_size(total),
+            rate,
+            msg
+        ),
+        None if pos > 0 => format!("{}  {}  {}", format_size(pos), rate, msg),
+        None => msg,
+    }
+}
+
+// ─────────────────────────────────────────────
+//  UI HELPERS
+// ─────────────────────────────────────────────
+
+/// Set once in `main` when color is off — output falls back to plain ASCII
+static PLAIN_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+fn plain_output() -> bool {
+    PLAIN_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Set once in `main` for --quiet: `info` and `step` lines are dropped
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Set once in `main` when stdout carries data (`info --json`): the status
+/// lines below go to stderr instead, and there is no banner
+static DATA_STDOUT: AtomicBool = AtomicBool::new(false);
+
+fn data_stdout() -> bool {
+    DATA_STDOUT.load(Ordering::Relaxed)
+}
+
+/// One status line, on whichever stream isn't reserved for data
+fn say(line: std::fmt::Arguments) {
+    if data_stdout() {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+fn print_banner() {
+    if plain_output() {
+        println!("+----------------------------------------------------------+");
+        println!("|              BURNENGINE USB v3.0                         |");
+        println!("|          Real - Safe - Linux ISO to USB Writer           |");
+        println!("+----------------------------------------------------------+");
+        println!();
+        return;
+    }
+    println!("{}", "╔══════════════════════════════════════════════════════════╗".bright_cyan());
+    println!("{} {} {}",
+        "║".bright_cyan(),
+        "     🔥  B U R N E N G I N E  U S B  v3.0  🔥          ".bright_yellow().bold(),
+        "║".bright_cyan()
+    );
+    println!("{} {} {}",
+        "║".bright_cyan(),
+        "       Real · Safe · Linux ISO to USB Writer          ".bright_white(),
+        "  ║".bright_cyan()
+    );
+    println!("{}", "╚══════════════════════════════════════════════════════════╝".bright_cyan());
+    println!();
+}
+
+/// Pick the emoji icon, or its ASCII stand-in when output is plain
+fn icon(fancy: &'static str, plain: &'static str) -> &'static str {
+    if plain_output() { plain } else { fancy }
+}
+
+fn info(msg: &str) {
+    log::info!("{}", strip_ansi(msg));
+    if quiet() {
+        return;
+    }
+    say(format_args!("{} {}", icon("ℹ️ ", "[i]").blue(), msg.bright_white()));
+}
+fn success(msg: &str) {
+    log::info!("{}", strip_ansi(msg));
+    say(format_args!("{} {}", icon("✅", "[ok]").green(), msg.bright_green().bold()));
+}
+fn warn(msg: &str) {
+    log::warn!("{}", strip_ansi(msg));
+    say(format_args!("{} {}", icon("⚠️ ", "[!]").yellow(), msg.yellow()));
+}
+fn err_msg(msg: &str) {
+    log::error!("{}", strip_ansi(msg));
+    say(format_args!("{} {}", icon("❌", "[x]").red(), msg.bright_red().bold()));
+}
+fn step(n: u8, t: u8, msg: &str) {
+    log::info!("[{}/{}] {}", n, t, strip_ansi(msg));
+    if quiet() {
+        return;
+    }
+    say(format_args!("{} {}",
+        format!("[{}/{}]", n, t).bright_cyan().bold(),
+        msg.white()
+    ));
+}
+
+const ASCII_TICKS: [&str; 4] = ["|", "/", "-", "\\"];
+
+fn spinner_style() -> ProgressStyle {
+    if plain_output() {
+        return ProgressStyle::with_template("{spinner} {msg}")
+            .unwrap()
+            .tick_strings(&ASCII_TICKS);
+    }
+    ProgressStyle::with_template("{spinner:.cyan} {msg}")
+        .unwrap()
+        .tick_strings(&["⠋","⠙","⠹","⠸","⠼","⠴","⠦","⠧","⠇","⠏"])
+}
+
+fn plain_bar_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner} [{bar:50}] {percent}%  {msg}")
+        .unwrap()
+        .tick_strings(&ASCII_TICKS)
+        .progress_chars("=> ")
+}
+
+fn write_bar_style() -> ProgressStyle {
+    if plain_output() {
+        return plain_bar_style();
+    }
+    ProgressStyle::with_template(
+        "{spinner:.red} [{bar:50.red/dim}] {percent}%  {msg}"
+    )
+    .unwrap()
+    .tick_strings(&["🔥","💥","🔥","💥"])
+    .progress_chars("█▉▊▋▌▍▎▏ ")
+}
+
+fn verify_bar_style() -> ProgressStyle {
+    if plain_output() {
+        return plain_bar_style();
+    }
+    let template = match units() {
+        Units::Si => "{spinner:.green} [{bar:50.green/dim}] {percent}%  🔍 {decimal_bytes_per_sec}  🕐 ETA {eta}  {msg}",
+        Units::Iec => "{spinner:.green} [{bar:50.green/dim}] {percent}%  🔍 {binary_bytes_per_sec}  🕐 ETA {eta}  {msg}",
+    };
+    ProgressStyle::with_template(template)
+    .unwrap()
+    .tick_strings(&["🔍","🔎","✅","🔍"])
+    .progress_chars("█▉▊▋▌▍▎▏ ")
+}
+
+// ─────────────────────────────────────────────
+//  SELECT DRIVE
+// ─────────────────────────────────────────────
+
+/// `min_size` (image bytes): smaller drives show "(too small)" and can't be chosen.
+/// The last entry rescans, so a stick plugged in late doesn't need a restart.
+fn select_usb_device(scanner: &DeviceScanner, min_size: Option<u64>) -> Result<UsbDevice> {
+    use std::io::IsTerminal;
+    // Without a terminal there's nobody to plug a stick in and rescan
+    let interactive = std::io::stdin().is_terminal();
+    let no_drives = || anyhow::Error::new(BurnError::NoDevicesFound);
+    let theme = ColorfulTheme::default();
+    let rescan = "🔄 Rescan devices";
+    let choose = "👉 Choose a drive (↑/↓)";
+
+    // With a known image size, drives that can't hold it are marked and can't be picked
+    let too_small = |d: &UsbDevice| min_size.is_some_and(|need| d.size < need);
+    let mut devices = scanner.devices().to_vec();
+
+    loop {
+        if devices.is_empty() {
+            if !interactive {
+                return Err(no_drives());
+            }
+            warn("No USB drives detected — plug one in, then rescan.");
+            let choice = Select::with_theme(&theme)
+                .with_prompt("🔌 No USB drive found")
+                .items(&[rescan, "Quit"])
+                .default(0)
+                .interact()?;
+            if choice == 1 {
+                return Err(no_drives());
+            }
+            devices = detect_usb_drives(&RealSysfs);
+            continue;
+        }
+
+        if !interactive && devices.iter().all(too_small) {
+            return Err(fail(EXIT_TOO_SMALL, anyhow!(
+                "Every detected USB drive is smaller than the image ({} needed)",
+                format_size(min_size.unwrap_or(0))
+            )));
+        }
+
+        // With several drives nothing is preselected: a stray Enter must not
+        // pick whichever stick happens to be listed first
+        let eligible = devices.iter().filter(|d| !too_small(d)).count();
+        let several = eligible > 1;
+        if several && !interactive {
+            return Err(fail(EXIT_NO_DEVICE, anyhow!(
+                "{} USB drives could take the image — name the target with --device",
+                eligible
+            )));
+        }
+        // No terminal for the menu: the one drive that fits is the only choice
+        if !interactive {
+            if let Some(only) = devices.iter().find(|d| !too_small(d)) {
+                info(&format!("Using {}, the only USB drive that fits the image", only.path.bright_cyan()));
+                return Ok(only.clone());
+            }
+        }
+
+        // Plain strings for dialoguer, with the rescan entry last
+        let mut plain_labels: Vec<String> = devices.iter().map(|d| {
+            let mark = if too_small(d) { "  (too small)" } else { "" };
+            format!("{}  {}  {}{}", d.path, d.size_human(), d.model, mark)
+        }).collect();
+        plain_labels.push(rescan.to_string());
+        if several {
+            plain_labels.insert(0, choose.to_string());
+        }
+        let first_fit = devices.iter().position(|d| !too_small(d)).unwrap_or(devices.len());
+
+        let picked = Select::with_theme(&theme)
+            .with_prompt("🔌 Select USB drive")
+            .items(&plain_labels)
+            .default(if several { 0 } else { first_fit })
+            .interact()?;
+        if several && picked == 0 {
+            warn("Several USB drives are connected — move to the one to write and press Enter.");
+            continue;
+        }
+        let idx = if several { picked - 1 } else { picked };
+
+        if idx == devices.len() {
+            devices = detect_usb_drives(&RealSysfs);
+            continue;
+        }
+        if !too_small(&devices[idx]) {
+            return Ok(devices[idx].clone());
+        }
+        warn(&format!(
+            "{} is only {} — the image needs {}. Pick another drive.",
+            devices[idx].path,
+            devices[idx].size_human(),
+            format_size(min_size.unwrap_or(0))
+        ));
+    }
+}
+
+/// A lazy device scan; `--verbose` also prints where its time went
+fn scanner(verbose: bool) -> DeviceScanner {
+    let scanner = DeviceScanner::new();
+    if verbose { scanner.report_timings(print_detect_timings) } else { scanner }
+}
+
+/// `--verbose`: where the device scan spent its time
+fn print_detect_timings(t: &DetectTimings) {
+    let ms = |d: Duration| format!("{:.1} ms", d.as_secs_f64() * 1000.0);
+    info(&format!(
+        "Device scan: {} block devices in {} ({} thread{})",
+        t.block_devices,
+        ms(t.total),
+        t.threads,
+        if t.threads == 1 { "" } else { "s" }
+    ));
+    info(&format!(
+        "  enumerate {}  ·  sysfs reads {}  ·  transport {}",
+        ms(t.enumerate), ms(t.sysfs_reads), ms(t.transport)
+    ));
+}
+
+/// Poll until a new USB drive shows up; several at once → ask which one
+fn wait_for_usb(running: Arc<AtomicBool>) -> Result<UsbDevice> {
+    let mut known: Vec<String> = detect_usb_drives(&RealSysfs).into_iter().map(|d| d.path).collect();
+
+    let sp = ProgressBar::new_spinner();
+    hide_if_machine(&sp);
+    sp.set_style(spinner_style());
+    sp.set_message("Waiting for USB insertion…");
+    sp.enable_steady_tick(Duration::from_millis(80));
+
+    while running.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(500));
+        let current = detect_usb_drives(&RealSysfs);
+        let mut new: Vec<UsbDevice> = current.iter()
+            .filter(|d| !known.contains(&d.path))
+            .cloned()
+            .collect();
+
+        match new.len() {
+            0 => {
+                // Forget drives that were pulled so re-inserting one counts as new
+                known.retain(|p| current.iter().any(|d| &d.path == p));
+            }
+            1 => {
+                let dev = new.remove(0);
+                sp.finish_with_message(format!("🔌 Detected {}  {}  {}",
+                    dev.path.bright_cyan().bold(),
+                    dev.size_human().bright_white(),
+                    dev.model.yellow()
+                ));
+                return Ok(dev);
+            }
+            _ => {
+                sp.finish_with_message("🔌 Several USB drives appeared");
+                return select_usb_device(&DeviceScanner::from_devices(new), None);
+            }
+        }
+    }
+
+    sp.abandon_with_message("⚠️  interrupted".yellow().to_string());
+    Err(anyhow!("Interrupted while waiting for a USB drive"))
+}
+
+fn pick_file() -> Result<PathBuf> {
+    let theme = ColorfulTheme::default();
+
+    if std::env::var("DISPLAY").is_ok() || std::env::var("WAYLAND_DISPLAY").is_ok() {
+        let use_gui = Confirm::with_theme(&theme)
+            .with_prompt("📂 Open graphical file picker?")
+            .default(true)
+            .interact()?;
+        if use_gui {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("Disk Images (.iso, .img)", &["iso", "img"])
+                .add_filter("Compressed Images (.img.xz, .gz, .zst)", &["img.xz", "xz", "gz", "zst"])
+                .add_filter("All Files", &["*"])
+                .set_title("Select an ISO or disk image")
+                .pick_file()
+            {
+                return Ok(path);
+            }
+        }
+    }
+
+    let s: String = dialoguer::Input::with_theme(&theme)
+        .with_prompt("📁 Path to ISO / .img file")
+        .interact_text()?;
+    let p = PathBuf::from(s.trim());
+    if !p.exists() {
+        return Err(anyhow!("File not found: {}", p.display()));
+    }
+    Ok(p)
+}
+
+// ─────────────────────────────────────────────
+//  SAFETY CONFIRMATION
+// ─────────────────────────────────────────────
+
+/// Check the image against `<iso>.sha256`, `<iso>.md5` or `SHA256SUMS` if one is there
+/// → true if a checksum file was found, i.e. the whole image was just read
+fn verify_source(iso: &Path) -> Result<bool> {
+    let Some(sum) = find_source_checksum(iso) else {
+        info("No checksum file next to the image (.sha256 / .md5 / SHA256SUMS) — source not verified");
+        return Ok(false);
+    };
+
+    let pb = ProgressBar::new(0);
+    hide_if_machine(&pb);
+    pb.set_style(verify_bar_style());
+    pb.set_message(format!(
+        "Checking image {} against {}…",
+        sum.kind.describe(),
+        sum.file.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    pb.enable_steady_tick(Duration::from_millis(120));
+    let actual = hash_file(iso, sum.kind, BarProgress::new(pb.clone(), "source").as_ref())?;
+
+    if actual == sum.expected {
+        pb.finish_with_message(format!("{}", format!("✅ Source {} matches {}", sum.kind.describe(), sum.file.display()).green()));
+        Ok(true)
+    } else {
+        pb.abandon_with_message(format!("{}", "❌ Source checksum mismatch".red()));
+        log::error!("source {} expected {} got {}", sum.kind.describe(), sum.expected, actual);
+        Err(anyhow!(
+            "{} does not match {}:\n  expected {}\n  got      {}\n\
+             The download is probably corrupt — download it again \
+             (or pass --no-source-verify to write it anyway).",
+            iso.display(), sum.file.display(), sum.expected, actual
+        ))
+    }
+}
+
+/// --precheck-source: read the image end to end before anything is touched
+fn precheck_source(iso: &Path) -> Result<()> {
+    let pb = ProgressBar::new(0);
+    hide_if_machine(&pb);
+    pb.set_style(verify_bar_style());
+    pb.set_message("Reading the whole image…");
+    pb.enable_steady_tick(Duration::from_millis(120));
+    match read_through(iso, BarProgress::new(pb.clone(), "precheck").as_ref()) {
+        Ok(bytes) => {
+            pb.finish_with_message(format!("{}", format!("✅ Image readable ({})", format_size(bytes)).green()));
+            Ok(())
+        }
+        Err(e) => {
+            pb.abandon_with_message(format!("{}", "❌ Image read error".red()));
+            Err(anyhow::Error::new(e).context("The image can't be read completely — the USB was not touched. \
+                           Copy the image to another disk and try again."))
+        }
+    }
+}
+
+/// Refuse a device that is part of an md array or LVM volume group — a
+/// mistake no confirmation prompt should be able to wave through
+fn check_raid_lvm(device: &UsbDevice, opts: &WriteOptions) -> Result<()> {
+    let found = raid_lvm_membership(device);
+    if found.is_empty() {
+        return Ok(());
+    }
+    if opts.allow_raid {
+        for f in &found {
+            warn(f);
+        }
+        warn("--i-know-its-raid: overwriting it anyway");
+        return Ok(());
+    }
+    Err(anyhow!(
+        "{} looks like part of a RAID array or LVM volume group:\n{}\n\
+         Overwriting it can break that array or volume group. If that's really what you want, \
+         pass --i-know-its-raid (--force is not enough).",
+        device.path,
+        found.iter().map(|f| format!("  • {}", f)).collect::<Vec<_>>().join("\n")
+    ))
+}
+
+fn safety_confirm(iso: &PathBuf, device: &UsbDevice, opts: &WriteOptions) -> Result<bool> {
+    check_raid_lvm(device, opts)?;
+    // Streamed images can only be read once, by the write itself
+    let streamed = is_streamed(iso);
+    // A checksum check already reads every byte — no need to read twice
+    let read_whole = opts.source_verify && !streamed && verify_source(iso)?;
+    if opts.precheck_source && !read_whole {
+        precheck_source(iso)?;
+    }
+    let iso_bytes = source_size(iso)?;
+    let compressed = compression_tool(iso).is_some();
+    let size_known = match streamed {
+        true => iso_bytes.is_some(),
+        false => !compressed || decompressed_size(iso).is_some(),
+    };
+    let iso_bytes = iso_bytes.unwrap_or(0);
+    let kind = match streamed {
+        true => None,
+        false => Some(inspect_iso(iso)?),
+    };
+    let theme = ColorfulTheme::default();
+
+    let large = device.size > opts.large_device_bytes;
+    let protected = protected_mark(device);
+    if quiet() {
+        println!(
+            "{} ({}) → {} ({}, {}) — all data on the device will be erased",
+            iso.display(), format_size(iso_bytes), device.path, device.model, device.size_human()
+        );
+    } else {
+        println!();
+        println!("{}", "┌─────────────────────────────────────────────────────┐".bright_red());
+        println!("{} {} {}",
+            "│".bright_red(),
+            "           ⚠️   WARNING — DATA WILL BE LOST!  ⚠️           ".bright_red().bold(),
+            "│".bright_red()
+        );
+        println!("{}", "├─────────────────────────────────────────────────────┤".bright_red());
+        println!("{}  {:20} {}  {}",
+            "│".bright_red(),
+            "Image:".bright_white(),
+            iso.file_name().unwrap_or_default().to_string_lossy().bright_yellow(),
+            "│".bright_red()
+        );
+        println!("{}  {:20} {}  {}",
+            "│".bright_red(),
+            "Image size:".bright_white(),
+            match (streamed, size_known) {
+                (true, false) => "unknown (streamed)".to_string(),
+                _ => format!("{}{}", format_size(iso_bytes),
+                    match (compressed, size_known) {
+                        (false, _) => "",
+                        (true, true) => " (uncompressed)",
+                        (true, false) => " (compressed)",
+                    }
+                ),
+            }.bright_yellow(),
+            "│".bright_red()
+        );
+        println!("{}  {:20} {}  {}",
+            "│".bright_red(),
+            "Image type:".bright_white(),
+            kind.map_or("streamed — not inspected", |k| k.describe()).bright_yellow(),
+            "│".bright_red()
+        );
+        println!("{}  {:20} {}  {}",
+            "│".bright_red(),
+            "Target device:".bright_white(),
+            device.path.bright_red().bold(),
+            "│".bright_red()
+        );
+        println!("{}  {:20} {}  {}",
+            "│".bright_red(),
+            "Device model:".bright_white(),
+            device.model.bright_yellow(),
+            "│".bright_red()
+        );
+        println!("{}  {:20} {}  {}",
+            "│".bright_red(),
+            "Device size:".bright_white(),
+            device.size_human().bright_yellow(),
+            "│".bright_red()
+        );
+        print_device_contents(device);
+        if let Some(mark) = &protected {
+            println!("{}  {:20} {}  {}",
+                "│".bright_red(),
+                "⚠ PROTECTED:".bright_white(),
+                format!("reference media ({})", mark.name).bright_red().bold(),
+                "│".bright_red()
+            );
+        }
+        if let Some(range) = opts.range {
+            println!("{}  {:20} {}  {}",
+                "│".bright_red(),
+                "PARTIAL write:".bright_white(),
+                range.describe().bright_red().bold(),
+                "│".bright_red()
+            );
+        }
+        if opts.discard {
+            println!("{}  {:20} {}  {}",
+                "│".bright_red(),
+                "Before writing:".bright_white(),
+                "discard (TRIM) EVERY block".bright_red().bold(),
+                "│".bright_red()
+            );
+        }
+        if opts.zap {
+            println!("{}  {:20} {}  {}",
+                "│".bright_red(),
+                "Before writing:".bright_white(),
+                "zero first + last 1 MiB (old MBR/GPT)".bright_yellow(),
+                "│".bright_red()
+            );
+        }
+        if let Some(extra) = opts.extra_partition {
+            println!("{}  {:20} {}  {}",
+                "│".bright_red(),
+                "Extra partition:".bright_white(),
+                format!("exFAT/FAT32, {}", extra.describe()).bright_yellow(),
+                "│".bright_red()
+            );
+        }
+        if large {
+            println!("{}  {} {}",
+                "│".bright_red(),
+                format!(
+                    "⚠️  {} is unusually large for a USB stick —",
+                    device.size_human()
+                ).bright_red().bold(),
+                "│".bright_red()
+            );
+            println!("{}  {} {}",
+                "│".bright_red(),
+                "   it may be a USB HDD holding backups!".bright_red().bold(),
+                "│".bright_red()
+            );
+        }
+        println!("{}", "│                                                     │".bright_red());
+        println!("{} {} {}",
+            "│".bright_red(),
+            "  ALL DATA ON THIS USB WILL BE PERMANENTLY ERASED!   ".bright_red().bold(),
+            "│".bright_red()
+        );
+        println!("{}", "└─────────────────────────────────────────────────────┘".bright_red());
+        println!();
+    }
+
+    if streamed && !size_known {
+        warn("Image size unknown — it can't be checked against the USB before writing");
+    } else if !size_known {
+        warn("Uncompressed size unknown — the fit check uses the compressed size");
+    }
+
+    // Check the image fits on device
+    if let Some(range) = opts.range {
+        if range.end() > device.size {
+            return Err(fail(EXIT_TOO_SMALL, anyhow!(
+                "Partial write ends at byte {} — past the end of the USB ({} bytes)!",
+                range.end(),
+                device.size
+            )));
+        }
+    } else if iso_bytes > device.size {
+        return Err(BurnError::DeviceTooSmall { iso: iso_bytes, device: device.size }.into());
+    }
+
+    // Not obviously bootable? Ask before flashing garbage
+    if let Some(kind) = kind.filter(|k| !k.has_boot_signature() && !opts.force) {
+        warn(&format!(
+            "{} does not look like a bootable image ({}).",
+            iso.display(),
+            kind.describe()
+        ));
+        let go_on = Confirm::with_theme(&theme)
+            .with_prompt("This file does not look like a bootable image — continue?")
+            .default(false)
+            .interact()?;
+        if !go_on {
+            warn("Cancelled.");
+            return Ok(false);
+        }
+    }
+
+    // Quiet: one confirmation (none with --force), plus the typed name for big drives
+    if quiet() {
+        if opts.force {
+            return Ok(confirm_protected(protected.as_ref(), opts)? && confirm_phrase(opts)?);
+        }
+        let go = Confirm::with_theme(&theme)
+            .with_prompt(format!("Write the image — {}?", erase_summary(device)))
+            .default(false)
+            .interact()?;
+        return Ok(go
+            && confirm_protected(protected.as_ref(), opts)?
+            && confirm_typed_name(device, large)?
+            && confirm_phrase(opts)?);
+    }
+
+    // Double confirmation
+    let first = Confirm::with_theme(&theme)
+        .with_prompt(format!("Write to {}? ({})", device.path, device.model))
+        .default(false)
+        .interact()?;
+
+    if !first {
+        warn("Cancelled.");
+        return Ok(false);
+    }
+
+    Ok(confirm_protected(protected.as_ref(), opts)?
+        && confirm_final(device, large, opts)?
+        && confirm_phrase(opts)?)
+}
+
+/// The `--protect` mark on the stick, if it has one. Reading it needs access
+/// to the device; anything unreadable counts as unprotected.
+fn protected_mark(device: &UsbDevice) -> Option<DeviceMark> {
+    if device.is_file_target() {
+        return None;
+    }
+    read_mark(device).ok().flatten().filter(|m| m.protected)
+}
+
+/// A stick flagged with --protect: one more question before it's overwritten
+/// (--force only warns)
+fn confirm_protected(mark: Option<&DeviceMark>, opts: &WriteOptions) -> Result<bool> {
+    let Some(mark) = mark else { return Ok(true) };
+    warn(&format!(
+        "⚠ This stick was flagged as PROTECTED reference media ({}, {} ago)",
+        mark.name,
+        format_eta(mark_age(mark))
+    ));
+    if opts.force {
+        log::warn!("overwriting protected media ({}) with --force", mark.name);
+        return Ok(true);
+    }
+    let go = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Overwrite the protected reference stick anyway?")
+        .default(false)
+        .interact()?;
+    if !go {
+        warn("Cancelled — the reference stick is untouched.");
+    }
+    Ok(go)
+}
+
+/// `--protect` after the mark: the kernel ro flag on top. It's a soft guard
+/// that lasts until the stick is replugged; the mark is what persists.
+fn protect_device(device: &UsbDevice) {
+    if find_tool("blockdev").is_none() {
+        warn("--protect: blockdev (util-linux) is not installed — only the mark was written");
+        return;
+    }
+    match set_read_only(device, true) {
+        Ok(()) => {
+            success(&format!("🔒 {} flagged as protected reference media", device.path));
+            info("The read-only flag resets when the stick is replugged; the protected mark stays.");
+        }
+        Err(e) => warn(&format!("--protect: {:#} — only the mark was written", e)),
+    }
+}
+
+/// `--confirm-string`: the exact phrase must be typed; a mismatch cancels
+fn confirm_phrase(opts: &WriteOptions) -> Result<bool> {
+    let Some(phrase) = &opts.confirm_string else { return Ok(true) };
+    let typed: String = dialoguer::Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("Type '{}' to start writing", phrase))
+        .allow_empty(true)
+        .interact_text()?;
+    if typed.trim() != phrase.trim() {
+        warn("Confirmation phrase did not match — cancelled.");
+        return Ok(false);
+    }
+    log::info!("confirmation phrase accepted");
+    Ok(true)
+}
+
+/// Warning-panel rows for what's on the stick now, so the user recognizes it.
+/// Nothing is printed if lsblk isn't available.
+fn print_device_contents(device: &UsbDevice) {
+    let Some(filesystems) = device_filesystems(device) else { return };
+    let lines: Vec<String> = if filesystems.is_empty() {
+        vec!["no filesystems found".to_string()]
+    } else {
+        filesystems.iter().map(|fs| {
+            let mut line = fs.fstype.clone();
+            if let Some(label) = &fs.label {
+                line.push_str(&format!(" '{}'", label));
+            }
+            line.push_str(&format!(" ({})", fs.name));
+            if let Some(used) = fs.used {
+                line.push_str(&format!(" {} used", format_size(used)));
+            }
+            line
+        }).collect()
+    };
+    for (i, line) in lines.iter().enumerate() {
+        println!("{}  {:20} {}  {}",
+            "│".bright_red(),
+            if i == 0 { "Currently on device:" } else { "" }.bright_white(),
+            line.bright_yellow(),
+            "│".bright_red()
+        );
+    }
+}
+
+/// "erase /dev/sdb SanDisk Ultra (16.0 GB)" — keeps what is about to be lost
+/// in the prompt itself, even once the panel has scrolled away
+fn erase_summary(device: &UsbDevice) -> String {
+    format!("erase {} {} ({})", device.path, device.model, device.size_human())
+}
+
+/// Last line of defence shared by write and wipe: final yes/no, then
+/// the typed device name for unusually large drives
+fn confirm_final(device: &UsbDevice, large: bool, opts: &WriteOptions) -> Result<bool> {
+    let theme = ColorfulTheme::default();
+    let second = Confirm::with_theme(&theme)
+        .with_prompt(format!("⚠️  FINAL: {} — this CANNOT be undone. Continue?", erase_summary(device)))
+        .default(false)
+        .interact()?;
+
+    if !second {
+        return Ok(false);
+    }
+
+    confirm_typed_name(device, large && !opts.force)
+}
+
+/// Big drives: make the user type the device name, like balenaEtcher
+fn confirm_typed_name(device: &UsbDevice, large: bool) -> Result<bool> {
+    if !large {
+        return Ok(true);
+    }
+    let typed: String = dialoguer::Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "This is a {} drive. Type '{}' to confirm",
+            device.size_human(),
+            device.name
+        ))
+        .allow_empty(true)
+        .interact_text()?;
+    if typed.trim() != device.name {
+        warn("Device name did not match — cancelled.");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+// ─────────────────────────────────────────────
+//  UNMOUNT PARTITIONS
+// ─────────────────────────────────────────────
+
+/// `engine::unmount_device` with a line per mount; `dry_run` only lists them
+fn unmount_all(device: &UsbDevice, dry_run: bool) -> Result<()> {
+    if dry_run {
+        for (dev, point) in device_mounts(device) {
+            info(&format!("Would unmount {} ({})", point, dev));
+        }
+        unmount_device(device, true, &mut |_, _, _| {})?;
+        return Ok(());
+    }
+    info(&format!("Unmounting all partitions on {}…", device.path));
+    unmount_device(device, false, &mut |dev, point, done| {
+        if !done {
+            info(&format!("  Unmounting {} ({})…", point, dev));
+        }
+    })?;
+    Ok(())
+}
+
+/// What the current write unmounted, and whether dd (or the zap) has touched
+/// the device since — for `restore_mounts` after a failed write
+static UNMOUNTED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+static DEVICE_TOUCHED: AtomicBool = AtomicBool::new(false);
+
+/// After a failed or cancelled write: say which partitions were left unmounted
+/// and, while their data is still intact, offer to mount them again
+fn restore_mounts() {
+    use std::io::IsTerminal;
+    let mounts = std::mem::take(&mut *UNMOUNTED.lock().unwrap_or_else(|e| e.into_inner()));
+    if mounts.is_empty() {
+        return;
+    }
+    warn("These partitions were unmounted and are still unmounted:");
+    for (dev, point) in &mounts {
+        warn(&format!("  • {} (was on {})", dev, point));
+    }
+    if DEVICE_TOUCHED.load(Ordering::SeqCst) {
+        warn("The write had already started — their data is likely damaged, so they are left alone.");
+        return;
+    }
+    let offer = std::io::stdin().is_terminal() && find_tool("udisksctl").is_some();
+    if !offer
+        || !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Nothing was written — mount them again with udisksctl?")
+            .default(true)
+            .interact()
+            .unwrap_or(false)
+    {
+        for (dev, _) in mounts.iter().rev() {
+            info(&format!("To mount it again: udisksctl mount -b {}", dev));
+        }
+        return;
+    }
+    // Parents before what was mounted inside them
+    for (dev, _) in mounts.iter().rev() {
+        let ok = Command::new("udisksctl")
+            .args(["mount", "-b", dev])
+            .stdout(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success());
+        match ok {
+            true => success(&format!("Mounted {} again", dev)),
+            false => warn(&format!("Could not mount {} — try: udisksctl mount -b {}", dev, dev)),
+        }
+    }
+}
+
+/// Run `work` behind a spinner on `multi` — for the quiet stretches (unmount,
+/// zap) between the confirmation and the first bar movement
+fn with_spinner<T>(
+    multi: &MultiProgress,
+    msg: &str,
+    done: &str,
+    work: impl FnOnce(&ProgressBar) -> Result<T>,
+) -> Result<T> {
+    let sp = add_bar(multi, ProgressBar::new_spinner()).with_style(spinner_style());
+    sp.set_message(msg.to_string());
+    sp.enable_steady_tick(Duration::from_millis(80));
+    let result = work(&sp);
+    match &result {
+        Ok(_) => sp.finish_with_message(format!("{}", done.green())),
+        Err(_) => sp.abandon_with_message(format!("{}", "❌ failed".red())),
+    }
+    result
+}
+
+/// Open the device for writing before dd does, so the usual failures get a
+/// plain explanation instead of a bare dd exit code. O_EXCL makes the kernel
+/// refuse with EBUSY while anything still holds the disk or a partition.
+fn check_device_opens(device: &UsbDevice) -> Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    const O_EXCL: i32 = 0o200;
+    const EBUSY: i32 = 16;
+    let mut open = fs::OpenOptions::new();
+    open.write(true);
+    match device.is_file_target() {
+        // dd creates a missing --target-file; don't truncate an existing one
+        true => open.create(true).truncate(false),
+        false => open.custom_flags(O_EXCL),
+    };
+    let e = match open.open(&device.path) {
+        Ok(_) => return Ok(()),
+        Err(e) => e,
+    };
+    Err(match (e.kind(), e.raw_os_error()) {
+        (std::io::ErrorKind::PermissionDenied, _) => BurnError::PermissionDenied { path: device.path.clone() }.into(),
+        (_, Some(EBUSY)) => fail(
+            EXIT_WRITE_FAILED,
+            anyhow!("{} is busy — a partition is still mounted or in use (try: burn unmount)", device.path),
+        ),
+        (std::io::ErrorKind::NotFound, _) => fail(
+            EXIT_NO_DEVICE,
+            anyhow!("{} disappeared — was the stick unplugged?", device.path),
+        ),
+        _ => fail(EXIT_WRITE_FAILED, anyhow!(e).context(format!("Cannot open {} for writing", device.path))),
+    })
+}
+
+/// `burn unmount`: the same unmount a write does, on its own
+fn do_unmount(device: &UsbDevice, dry_run: bool) -> Result<()> {
+    let mounts = device_mounts(device);
+    if mounts.is_empty() {
+        success(&format!("Nothing on {} is mounted", device.path));
+        return Ok(());
+    }
+    unmount_all(device, dry_run)?;
+    if dry_run {
+        success("DRY-RUN complete — nothing was unmounted");
+        return Ok(());
+    }
+    success(&format!("Unmounted {} mountpoint(s) on {}:", mounts.len(), device.path));
+    for (dev, point) in &mounts {
+        info(&format!("  {}  ({})", dev, point));
+    }
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+//  WRITE — real dd
+// ─────────────────────────────────────────────
+
+/// Everything a write would do, saved by `--dry-run --output-image`.
+/// Deterministic: same inputs → same file (no timestamps).
+struct WritePlan {
+    source: String,
+    source_bytes: u64,
+    decompressor: Option<&'static str>,
+    device: String,
+    device_model: String,
+    device_bytes: u64,
+    device_sector_size: u64,
+    /// (source, mountpoint) pairs that would be unmounted
+    unmount: Vec<(String, String)>,
+    block_size: u64,
+    write_bytes: u64,
+    range: Option<WriteRange>,
+    hash: &'static str,
+    commands: Vec<String>,
+}
+
+impl WritePlan {
+    fn sectors(&self) -> u64 {
+        self.write_bytes.div_ceil(self.device_sector_size)
+    }
+
+    fn blocks(&self) -> u64 {
+        self.write_bytes.div_ceil(self.block_size)
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# burn write plan\n");
+        out.push_str(&format!("source          {}\n", self.source));
+        out.push_str(&format!("source_bytes    {}\n", self.source_bytes));
+        out.push_str(&format!("decompress      {}\n", self.decompressor.unwrap_or("none")));
+        out.push_str(&format!("device          {}\n", self.device));
+        out.push_str(&format!("device_model    {}\n", self.device_model));
+        out.push_str(&format!("device_bytes    {}\n", self.device_bytes));
+        match self.range {
+            Some(r) => out.push_str(&format!("range           {}..{}\n", r.offset, r.end())),
+            None => out.push_str("range           whole image\n"),
+        }
+        out.push_str(&format!("write_bytes     {}\n", self.write_bytes));
+        out.push_str(&format!("sectors         {} x {}\n", self.sectors(), self.device_sector_size));
+        out.push_str(&format!("block_size      {} ({} blocks)\n", format_block_size(self.block_size), self.blocks()));
+        out.push_str(&format!("hash            {}\n", self.hash));
+        out.push_str("unmount\n");
+        if self.unmount.is_empty() {
+            out.push_str("  (nothing mounted)\n");
+        }
+        for (dev, point) in &self.unmount {
+            out.push_str(&format!("  {} on {}\n", dev, point));
+        }
+        out.push_str("commands\n");
+        for c in &self.commands {
+            out.push_str(&format!("  {}\n", c));
+        }
+        out
+    }
+
+    fn to_json(&self) -> String {
+        let unmount: Vec<String> = self.unmount.iter()
+            .map(|(dev, point)| format!(
+                "{{\"source\":{},\"mountpoint\":{}}}", json_str(dev), json_str(point)
+            ))
+            .collect();
+        let commands: Vec<String> = self.commands.iter().map(|c| json_str(c)).collect();
+        let range = match self.range {
+            Some(r) => format!("{{\"offset\":{},\"len\":{}}}", r.offset, r.len),
+            None => "null".into(),
+        };
+        format!(
+            "{{\"source\":{},\"source_bytes\":{},\"decompress\":{},\"device\":{},\
+             \"device_model\":{},\"device_bytes\":{},\"range\":{},\"write_bytes\":{},\
+             \"sectors\":{},\"sector_size\":{},\"block_size\":{},\"blocks\":{},\"hash\":{},\
+             \"unmount\":[{}],\"commands\":[{}]}}\n",
+            json_str(&self.source),
+            self.source_bytes,
+            self.decompressor.map(json_str).unwrap_or_else(|| "null".into()),
+            json_str(&self.device),
+            json_str(&self.device_model),
+            self.device_bytes,
+            range,
+            self.write_bytes,
+            self.sectors(),
+            self.device_sector_size,
+            self.block_size,
+            self.blocks(),
+            json_str(self.hash),
+            unmount.join(","),
+            commands.join(",")
+        )
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let json = path.extension().and_then(|e| e.to_str()) == Some("json");
+        let body = if json { self.to_json() } else { self.to_text() };
+        fs::write(path, body)
+            .with_context(|| format!("Cannot write plan: {}", path.display()))
+    }
+}
+
+/// Quote a string for JSON output
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// What actually happened during a write — reported even if verify fails
+#[derive(Debug, Clone, Default)]
+struct WriteOutcome {
+    bytes_written: u64,
+    synced: bool,
+    /// None = not requested, Some(false) = failed or mismatched
+    verify_result: Option<bool>,
+    /// None = not requested
+    boot_ok: Option<bool>,
+    /// None = not requested
+    ejected: Option<bool>,
+    /// Device already matched the ISO, nothing was written
+    skipped: bool,
+    /// Average speed of the dd pass in bytes/s
+    throughput: Option<u64>,
+    /// Label the data partition ended up with
+    data_label: Option<String>,
+    duration: Duration,
+}
+
+/// Bytes per second over the whole pass (None when there's nothing to measure)
+fn average_speed(bytes: u64, elapsed: Duration) -> Option<u64> {
+    let secs = elapsed.as_secs_f64();
+    (bytes > 0 && secs > 0.0).then(|| (bytes as f64 / secs) as u64)
+}
+
+fn do_write(
+    input: &PathBuf,
+    device: &UsbDevice,
+    opts: &WriteOptions,
+    running: Arc<AtomicBool>,
+) -> Result<WriteOutcome> {
+    let WriteOptions { verify, dry_run, verbose, .. } = *opts;
+    let started = Instant::now();
+    summarize(|s| s.target(input, device));
+    // 0 = a streamed image of unknown length
+    let iso_bytes = source_size(input)?.unwrap_or(0);
+    // A 0-byte file would "write" nothing, then pass verify on zero bytes
+    if iso_bytes == 0 && !is_streamed(input) {
+        return Err(anyhow!("{} is empty — nothing to write", input.display()));
+    }
+    // Bytes dd will copy
+    let write_bytes = opts.range.map_or(iso_bytes, |r| r.len);
+    let decompressor = compression_tool(input);
+    let total_steps: u8 = 2 + verify as u8 + opts.extra_partition.is_some() as u8;
+    let multi = phase_multi();
+
+    gap(&multi);
+    step(1, total_steps, "Preparing…");
+    info(&format!("Image: {}  ({}{})",
+        input.display().to_string().bright_yellow(),
+        match iso_bytes {
+            0 if is_streamed(input) => "size unknown".to_string(),
+            n => format_size(n),
+        },
+        if decompressor.is_some() { " uncompressed" } else { "" }
+    ));
+    info(&format!("USB  : {}  {}  {}",
+        device.path.bright_cyan(),
+        device.size_human().bright_white(),
+        device.model.yellow()
+    ));
+    if let Some(range) = opts.range {
+        warn(&format!("PARTIAL write — {}", range.describe()));
+    }
+    if dry_run { warn("DRY-RUN — nothing will be written"); }
+    gap(&multi);
+
+    // ── Make sure it's still the same stick ───
+    revalidate_device(&RealSysfs, device)?;
+    if device.is_file_target() {
+        warn("--target-file: skipping the block-device check — the target is a regular file");
+    } else if !is_block_device(&device.path) {
+        return Err(fail(EXIT_NO_DEVICE, anyhow!(
+            "{} is not a block device — refusing to write to it",
+            device.path
+        )));
+    }
+    if opts.direct {
+        check_direct(device, opts)?;
+    }
+
+    // ── Already flashed? ──────────────────────
+    if opts.skip_if_verified && dry_run {
+        info(&format!(
+            "Would read back {} from {} and skip the write if it already matches",
+            format_size(iso_bytes),
+            device.path
+        ));
+        gap(&multi);
+    } else if opts.skip_if_verified {
+        info("Checking whether the USB already matches the image…");
+        if device_matches_iso(input, device, opts, &multi, running.clone())? {
+            success("Device already matches the image, nothing to do");
+            return Ok(WriteOutcome {
+                skipped: true,
+                verify_result: Some(true),
+                duration: started.elapsed(),
+                ..Default::default()
+            });
+        }
+        info("Device differs from the image — writing.");
+        gap(&multi);
+    }
+
+    // ── Marked with this image already? ──────
+    if opts.mark && mark_offset(device, iso_bytes).is_none() {
+        return Err(anyhow!(
+            "--mark: the image fills {} up to its last sector — no slack space for the mark",
+            device.path
+        ));
+    }
+    let current_mark = match (opts.mark || opts.check_mark) && !dry_run {
+        true => Some(image_mark(input, iso_bytes)?),
+        false => None,
+    };
+    if opts.check_mark && dry_run {
+        info(&format!("Would read the mark in the last sector of {} and skip the write if it names this image", device.path));
+        gap(&multi);
+    } else if let Some(current) = current_mark.as_ref().filter(|_| opts.check_mark) {
+        match read_mark(device) {
+            Ok(Some(m)) if m.matches(current) => {
+                success(&format!(
+                    "{} is marked with {} (written {} ago) — nothing to do",
+                    device.path, m.name, format_eta(mark_age(&m))
+                ));
+                return Ok(WriteOutcome { skipped: true, duration: started.elapsed(), ..Default::default() });
+            }
+            Ok(Some(m)) => info(&format!("Device is marked with {} ({}…), not this image — writing.", m.name, &m.md5[..m.md5.len().min(12)])),
+            Ok(None) => info("No mark on the device — writing."),
+            Err(e) => warn(&format!("Could not read the mark ({}) — writing.", e)),
+        }
+        gap(&multi);
+    }
+
+    // ── Unmount ───────────────────────────────
+    let mounts = device_mounts(device);
+    if dry_run {
+        unmount_all(device, true)?;
+    } else {
+        progress_event("unmount", &[]);
+        // Recorded one by one, so a failure or Ctrl-C halfway still knows
+        // what to offer to mount again
+        UNMOUNTED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        with_spinner(&multi, "Preparing device…", "✅ Device ready", |sp| {
+            Ok(unmount_device(device, false, &mut |dev, point, done| {
+                if done {
+                    UNMOUNTED.lock().unwrap_or_else(|e| e.into_inner()).push((dev.to_string(), point.to_string()));
+                } else {
+                    sp.set_message(format!("Preparing device… unmounting {} ({})", point, dev));
+                }
+            })?)
+        })?;
+    }
+    gap(&multi);
+
+    if dry_run {
+        // is_write_protected opens the device for writing — only the sysfs flag here
+        if is_kernel_read_only(device) {
+            match protected_mark(device) {
+                Some(_) => info(&format!("Would clear the read-only flag --protect set on {}", device.path)),
+                None => warn(&format!(
+                    "{} is read-only — the write would be refused; check the lock switch on the stick",
+                    device.path
+                )),
+            }
+        }
+    } else {
+        // Our own --protect ro flag: the user has already agreed to overwrite
+        if is_kernel_read_only(device) && protected_mark(device).is_some() {
+            match set_read_only(device, false) {
+                Ok(()) => info(&format!("Cleared the read-only flag --protect set on {}", device.path)),
+                Err(e) => warn(&format!("Could not clear the read-only flag: {:#}", e)),
+            }
+        }
+        if is_write_protected(device) {
+            return Err(anyhow!(
+                "{} appears write-protected — check the lock switch on the stick",
+                device.path
+            ));
+        }
+        check_device_opens(device)?;
+    }
+
+    let engine_opts = engine::WriteOptions {
+        block_size: opts.block_size,
+        range: opts.range,
+        dd_conv: opts.dd_conv.clone(),
+        dd_oflag: opts.dd_oflag.clone(),
+        // --verify-streaming hashes the ISO on its way into dd
+        hash_source: opts.verify_streaming,
+        timeout: opts.timeout,
+        sync_interval: Some(opts.sync_interval),
+        dd_status_none: opts.dd_status_none,
+        rate_limit: opts.rate_limit,
+        read_ahead: opts.read_buffer,
+        running: running.clone(),
+        ..engine::WriteOptions::new(input.clone(), device.clone())
+    };
+    let dd_cmdline = engine_opts.command_line();
+
+    if dry_run {
+        let mut commands = Vec::new();
+        if opts.discard && !device.is_file_target() {
+            commands.push(if supports_discard(device) {
+                format!("blkdiscard {}", device.path)
+            } else {
+                format!("skip --discard: {} doesn't support discard", device.path)
+            });
+        }
+        if opts.zap && !device.is_file_target() {
+            commands.push(format!(
+                "zero the first and last {} of {} (stale MBR/GPT)",
+                format_block_size(ZAP_BYTES), device.path
+            ));
+        }
+        commands.push(dd_cmdline.clone());
+        if opts.retry && opts.block_size > RETRY_BLOCK_SIZE {
+            commands.push(format!("if dd hits an I/O error: unmount again, retry once with bs={}", format_block_size(RETRY_BLOCK_SIZE)));
+        }
+        if opts.direct {
+            commands.push("if O_DIRECT is refused: write again with oflag=sync".to_string());
+        }
+        if let Some(every) = engine_opts.periodic_sync() {
+            commands.push(format!("while dd runs: fdatasync {} every {}", device.path, format_block_size(every)));
+        }
+        commands.push("sync".to_string());
+        if let Some(extra) = opts.extra_partition {
+            commands.push(format!(
+                "then add a data partition ({}) after {} and format it exFAT/FAT32{}",
+                extra.describe(),
+                format_size(iso_bytes),
+                opts.label.as_ref().map(|l| format!(", label {}", l)).unwrap_or_default()
+            ));
+        }
+        if opts.check_boot {
+            commands.push("then: check the first sectors for an MBR/GPT boot signature".into());
+        }
+        if opts.mark {
+            commands.push(format!("then: write a mark (image name, MD5, time) to the last sector of {}", device.path));
+        }
+        if opts.protect {
+            commands.push(format!("then: blockdev --setro {} (until replugged)", device.path));
+        }
+        if opts.eject {
+            commands.push(format!("then: udisksctl power-off -b {}", device.path));
+        }
+        if let Some(cmd) = &opts.after_command {
+            commands.push(format!("then, on success: sh -c {}", shell_quote(cmd)));
+        }
+
+        success("DRY-RUN complete — would run:");
+        for c in &commands {
+            info(c);
+        }
+
+        if let Some(path) = &opts.plan_path {
+            let plan = WritePlan {
+                source: input.display().to_string(),
+                source_bytes: iso_bytes,
+                decompressor,
+                device: device.path.clone(),
+                device_model: device.model.clone(),
+                device_bytes: device.size,
+                device_sector_size: device.logical_sector_size,
+                unmount: mounts,
+                block_size: opts.block_size,
+                write_bytes,
+                range: opts.range,
+                hash: if verify { "md5" } else { "none" },
+                commands,
+            };
+            plan.save(path)?;
+            success(&format!("Plan written to {}", path.display()));
+        }
+        return Ok(WriteOutcome::default());
+    }
+
+    // ── Write with dd ─────────────────────────
+    step(2, total_steps, "Writing image to USB…");
+    DEVICE_TOUCHED.store(true, Ordering::SeqCst);
+
+    if opts.discard && !device.is_file_target() {
+        if !supports_discard(device) {
+            info(&format!("{} doesn't support discard — skipping --discard", device.path));
+        } else if find_tool("blkdiscard").is_none() {
+            warn("--discard needs blkdiscard (util-linux, not installed) — skipped");
+        } else {
+            // Only a speed-up: the write that follows replaces the contents anyway
+            let discarded = with_spinner(
+                &multi,
+                "Discarding all blocks (TRIM)…",
+                "✅ All blocks discarded",
+                |_| Ok(discard_device(device)?),
+            );
+            if let Err(e) = discarded {
+                warn(&format!("Discard failed, writing anyway: {:#}", e));
+            }
+        }
+    }
+
+    if opts.zap && !device.is_file_target() {
+        with_spinner(
+            &multi,
+            "Clearing old partition tables (first and last 1 MiB)…",
+            "✅ Old partition tables cleared",
+            |_| Ok(zap_device(device)?),
+        )
+        .map_err(|e| fail(EXIT_WRITE_FAILED, e))?;
+    }
+
+    if verbose {
+        info(&format!("Running: {}", dd_cmdline));
+    }
+
+    let mut written = write_pass(engine_opts.clone(), write_bytes, &multi);
+    if let Err(e) = &written {
+        if opts.retry && opts.block_size > RETRY_BLOCK_SIZE && running.load(Ordering::SeqCst) && is_io_failure(e) {
+            warn(&format!(
+                "Write failed with an I/O error at bs={} — retrying with bs={}",
+                format_block_size(opts.block_size),
+                format_block_size(RETRY_BLOCK_SIZE)
+            ));
+            log::warn!("write failed ({:#}), retrying with bs={}", e, RETRY_BLOCK_SIZE);
+            // The failed write may have made the kernel re-read the partition
+            // table and automount what it found
+            unmount_all(device, false)?;
+            gap(&multi);
+            written = write_pass(
+                engine::WriteOptions { block_size: RETRY_BLOCK_SIZE, ..engine_opts.clone() },
+                write_bytes,
+                &multi,
+            );
+        }
+    }
+    if let Err(e) = &written {
+        if opts.direct && running.load(Ordering::SeqCst) && is_direct_refused(e) {
+            warn(&format!("{} refused O_DIRECT — writing again with oflag=sync", device.path));
+            log::warn!("oflag=direct failed ({:#}), falling back to oflag=sync", e);
+            gap(&multi);
+            written = write_pass(
+                engine::WriteOptions { dd_oflag: Some("sync".to_string()), ..engine_opts },
+                write_bytes,
+                &multi,
+            );
+        }
+    }
+    let written = written.map_err(|e| fail(EXIT_WRITE_FAILED, e))?;
+
+    let mut outcome = WriteOutcome {
+        bytes_written: written.bytes_written,
+        throughput: average_speed(written.bytes_written, written.duration),
+        ..Default::default()
+    };
+    if let Some(speed) = outcome.throughput {
+        log::info!("average write speed {} bytes/s", speed);
+        if speed < opts.slow_threshold && written.bytes_written >= SLOW_CHECK_MIN_BYTES {
+            warn(&format!(
+                "Average write speed was only {}/s (threshold {}/s) — the stick may be fake or worn out",
+                format_size(speed),
+                format_size(opts.slow_threshold)
+            ));
+        }
+    }
+
+    // ── Sync ──────────────────────────────────
+    progress_event("sync", &[]);
+    let sp = add_bar(&multi, ProgressBar::new_spinner());
+    sp.set_style(spinner_style());
+    sp.set_message("Flushing buffers to USB (sync)…");
+    sp.enable_steady_tick(Duration::from_millis(80));
+    outcome.synced = Command::new("sync").status().map(|s| s.success()).unwrap_or(false);
+    if outcome.synced {
+        sp.finish_with_message(format!("{}", "✅ Sync complete".green()));
+    } else {
+        sp.abandon_with_message(format!("{}", "⚠️  sync failed".yellow()));
+    }
+    gap(&multi);
+
+    // ── Boot sanity ───────────────────────────
+    if opts.check_boot {
+        let ok = match inspect_device(device) {
+            Ok(kind) => matches!(kind, ImageKind::HybridIso | ImageKind::DiskImage),
+            Err(e) => {
+                warn(&format!("Boot check failed: {}", e));
+                false
+            }
+        };
+        if ok {
+            success("Device has a valid boot signature (MBR/GPT)");
+        } else {
+            warn("No MBR/GPT boot signature on the device — the write may be truncated or the image isn't USB-bootable");
+        }
+        outcome.boot_ok = Some(ok);
+    }
+
+    // ── Verify ────────────────────────────────
+    if verify {
+        let passed = match do_verify(
+            input, device, (3, total_steps), Some(&written), opts, &multi, running.clone(),
+        ) {
+            Ok(()) => true,
+            Err(e) => {
+                err_msg(&format!("{}", e));
+                false
+            }
+        };
+        outcome.verify_result = Some(passed);
+        if !passed {
+            // Don't build on top of a bad copy
+            outcome.duration = started.elapsed();
+            return Ok(outcome);
+        }
+    }
+
+    // ── Mark ──────────────────────────────────
+    if let Some(current) = current_mark.filter(|_| opts.mark) {
+        let mark = DeviceMark { written: unix_now(), protected: opts.protect, ..current };
+        match write_mark(device, &mark) {
+            Ok(()) => {
+                success(&format!("Marked {} with {} (last sector)", device.path, mark.name));
+                if opts.protect {
+                    protect_device(device);
+                }
+            }
+            Err(e) => warn(&format!("Mark not written: {:#}", e)),
+        }
+    }
+
+    // ── Extra data partition ──────────────────
+    if let Some(extra) = opts.extra_partition {
+        progress_event("partition", &[]);
+        gap(&multi);
+        step(total_steps, total_steps, "Adding extra data partition…");
+        match create_extra_partition(device, iso_bytes, extra, opts.label.as_deref()) {
+            Ok(label) => outcome.data_label = label,
+            Err(e) => warn(&format!("Extra partition skipped: {}", e)),
+        }
+    }
+
+    // ── Eject ─────────────────────────────────
+    outcome.ejected = if opts.eject && !device.is_file_target() {
+        progress_event("eject", &[]);
+        gap(&multi);
+        match eject_device(device) {
+            Ok(()) => {
+                success(&format!("{} powered off — safe to remove", device.path));
+                Some(true)
+            }
+            Err(e) => {
+                warn(&format!("Could not power off {}: {}", device.path, e));
+                Some(false)
+            }
+        }
+    } else {
+        None
+    };
+
+    outcome.duration = started.elapsed();
+    progress_event("done", &[("bytes", Some(outcome.bytes_written))]);
+    Ok(outcome)
+}
+
+/// The mark this image would leave: name, MD5 of the file, logical size
+fn image_mark(input: &Path, iso_bytes: u64) -> Result<DeviceMark> {
+    let pb = ProgressBar::new(0);
+    hide_if_machine(&pb);
+    pb.set_style(verify_bar_style());
+    pb.set_message("Hashing the image for its mark…");
+    pb.enable_steady_tick(Duration::from_millis(120));
+    let md5 = hash_file(input, HashKind::Md5, BarProgress::new(pb.clone(), "hash").as_ref());
+    pb.finish_and_clear();
+    Ok(DeviceMark {
+        name: input.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        md5: md5?,
+        size: iso_bytes,
+        written: 0,
+        protected: false,
+    })
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn mark_age(mark: &DeviceMark) -> Duration {
+    Duration::from_secs(unix_now().saturating_sub(mark.written))
+}
+
+/// Hybrid ISOs are zapped unless --no-zap: they carry their own MBR/GPT, and
+/// a stale backup GPT at the end of the stick can make firmware reject it
+fn zap_by_default(input: &Path) -> bool {
+    !is_streamed(input) && inspect_iso(&input.to_path_buf()).is_ok_and(|kind| kind == ImageKind::HybridIso)
+}
+
+/// Bytes the image puts on the device — None for a stream of unknown length
+fn source_size(input: &Path) -> Result<Option<u64>> {
+    match is_streamed(input) {
+        true => Ok(stream_size(input)),
+        false => Ok(Some(image_size(&input.to_path_buf())?)),
+    }
+}
+
+/// What can't be done with an image that is read once, as it arrives
+fn check_streamed(input: &Path, opts: &WriteOptions) -> Result<()> {
+    let unsupported = [
+        (opts.skip_if_verified, "--skip-if-verified"),
+        (opts.mark, "--mark"),
+        (opts.check_mark, "--check-mark"),
+        (opts.checkpoint.is_some(), "--checkpoint"),
+        (opts.write_checksum.is_some(), "--write-checksum"),
+        (opts.verify_bytes.is_some(), "--verify-bytes"),
+        (opts.precheck_source, "--precheck-source"),
+        (opts.extra_partition.is_some(), "--extra-partition"),
+    ];
+    if let Some((_, flag)) = unsupported.iter().find(|(set, _)| *set) {
+        return Err(anyhow!("{} needs an image file — it can't be used with a streamed image", flag));
+    }
+    // Written as it arrives, so a compressed download would land on the stick compressed
+    if is_url(input) && Path::new(input.to_string_lossy().split(['?', '#']).next().unwrap_or_default())
+        .extension()
+        .is_some_and(|e| matches!(e.to_str(), Some("gz" | "xz" | "zst" | "zstd")))
+    {
+        return Err(anyhow!(
+            "{} is compressed — decompress it on the way in instead:\n  \
+             curl -fsSL '{}' | xz -dc | burn write --stdin",
+            input.display(),
+            input.display()
+        ));
+    }
+    Ok(())
+}
+
+/// `do_write` + `report_write`, then the `--notify` desktop notification and
+/// the `--after-command` hook (success only — its non-zero exit code is ours)
+fn write_and_report(
+    input: &PathBuf,
+    device: &UsbDevice,
+    opts: &WriteOptions,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    let awake = keep_awake_lock(opts);
+    let result = do_write(input, device, opts, running)
+        .and_then(|outcome| report_write(input, device, opts, &outcome).map(|()| outcome));
+    drop(awake);
+    if let Ok(outcome) = &result {
+        summarize(|s| {
+            s.bytes_written = Some(outcome.bytes_written);
+            s.throughput = outcome.throughput;
+            s.verified = outcome.verify_result;
+        });
+    }
+    if result.is_err() {
+        restore_mounts();
+    }
+    if opts.notify && !opts.dry_run {
+        notify_done(input, device, &result);
+    }
+    let outcome = result?;
+
+    if let Some(cmd) = opts.after_command.as_deref().filter(|_| !opts.dry_run) {
+        let code = run_after_command(cmd, input, device, &outcome)?;
+        if code != 0 {
+            return Err(fail(code, anyhow!("--after-command exited with status {}", code)));
+        }
+    }
+    Ok(())
+}
+
+/// Run the `--after-command` hook through `sh -c` → its exit code
+fn run_after_command(cmd: &str, input: &Path, device: &UsbDevice, outcome: &WriteOutcome) -> Result<i32> {
+    info(&format!("Running after-command: {}", cmd));
+    let verify = match outcome.verify_result {
+        Some(true) => "passed",
+        Some(false) => "failed",
+        None => "off",
+    };
+    let status = Command::new("sh")
+        .args(["-c", cmd])
+        .env("BURN_DEVICE", &device.path)
+        .env("BURN_ISO", input)
+        .env("BURN_BYTES", outcome.bytes_written.to_string())
+        .env("BURN_VERIFY", verify)
+        .status()
+        .context("Failed to run --after-command")?;
+    log::info!("after-command → {:?}", status.code());
+    // Killed by a signal: report it like a shell would
+    Ok(status.code().unwrap_or_else(|| {
+        use std::os::unix::process::ExitStatusExt;
+        128 + status.signal().unwrap_or(0)
+    }))
+}
+
+/// `--keep-awake`: a logind "sleep:idle" block inhibitor, held as long as
+/// systemd-inhibit runs. Its command is a `cat` on our pipe, so the lock goes
+/// when this is dropped — or when burn dies, however it dies.
+struct SleepInhibitor(std::process::Child);
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        drop(self.0.stdin.take());
+        let _ = self.0.wait();
+        log::info!("keep-awake: inhibitor released");
+    }
+}
+
+/// Take the inhibitor if `--keep-awake` asks for it; a warning if logind can't give one
+fn keep_awake_lock(opts: &WriteOptions) -> Option<SleepInhibitor> {
+    if !opts.keep_awake || opts.dry_run {
+        return None;
+    }
+    let unavailable = |why: &str| {
+        warn(&format!("--keep-awake: {} — suspend is NOT prevented, keep the machine awake yourself", why));
+        None
+    };
+    if find_tool("systemd-inhibit").is_none() {
+        return unavailable("systemd-inhibit not found");
+    }
+    let child = Command::new("systemd-inhibit")
+        .args([
+            "--what=sleep:idle",
+            "--who=burn",
+            "--why=Writing a USB drive",
+            "--mode=block",
+            "cat",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(c) => c,
+        Err(e) => return unavailable(&format!("cannot run systemd-inhibit ({})", e)),
+    };
+    // Without logind it gives up at once
+    thread::sleep(Duration::from_millis(200));
+    if let Ok(Some(status)) = child.try_wait() {
+        log::warn!("systemd-inhibit exited early: {}", status);
+        return unavailable("logind refused the inhibitor lock");
+    }
+    log::info!("keep-awake: holding a sleep:idle inhibitor (pid {})", child.id());
+    info("Suspend is blocked until burn finishes (--keep-awake)");
+    Some(SleepInhibitor(child))
+}
+
+/// Best effort: only with a graphical session, and quietly skipped if
+/// notify-send or a notification daemon is missing
+fn notify_done(input: &Path, device: &UsbDevice, result: &Result<WriteOutcome>) {
+    let has_session = ["DISPLAY", "WAYLAND_DISPLAY", "DBUS_SESSION_BUS_ADDRESS"]
+        .iter()
+        .any(|v| std::env::var_os(v).is_some());
+    if !has_session || find_tool("notify-send").is_none() {
+        log::info!("notify: no desktop session or notify-send, skipping");
+        return;
+    }
+
+    let iso = input.file_name().unwrap_or_default().to_string_lossy();
+    let (summary, urgency, icon) = match result {
+        Ok(_) => ("🔥 USB ready".to_string(), "normal", "drive-removable-media"),
+        Err(_) => ("❌ USB write failed".to_string(), "critical", "dialog-error"),
+    };
+    let body = match result {
+        Ok(_) => format!("{} → {} ({})", iso, device.path, device.model),
+        Err(e) => format!("{} → {}: {}", iso, device.path, e.to_string().lines().next().unwrap_or("")),
+    };
+    let status = Command::new("notify-send")
+        .args(["--app-name=burn", "-u", urgency, "-i", icon, &summary, &body])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    log::info!("notify-send → {:?}", status.map(|s| s.code()));
+}
+
+fn report_write(
+    input: &Path,
+    device: &UsbDevice,
+    opts: &WriteOptions,
+    outcome: &WriteOutcome,
+) -> Result<()> {
+    if opts.dry_run || outcome.skipped {
+        return Ok(());
+    }
+    let verify_failed = outcome.verify_result == Some(false);
+
+    if quiet() {
+        println!(
+            "{}: {} written to {} in {}{}",
+            if verify_failed { "VERIFY FAILED" } else { "done" },
+            format_size(outcome.bytes_written),
+            device.path,
+            format_eta(outcome.duration),
+            match outcome.verify_result {
+                Some(true) if opts.verify_bytes.is_some_and(|n| n < outcome.bytes_written) => ", partially verified",
+                Some(true) => ", verified",
+                _ => "",
+            }
+        );
+    } else {
+        print_write_summary(input, device, opts, outcome);
+    }
+
+    if verify_failed {
+        return Err(fail(EXIT_VERIFY_FAILED, anyhow!(
+            "Write finished but verification failed — the USB may be faulty. \
+             Try writing again or use a different drive."
+        )));
+    }
+    Ok(())
+}
+
+/// The banner-style result block and, with --verbose or a failed verify, the summary table
+fn print_write_summary(input: &Path, device: &UsbDevice, opts: &WriteOptions, outcome: &WriteOutcome) {
+    let verify_failed = outcome.verify_result == Some(false);
+
+    println!();
+    println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_cyan());
+    if verify_failed {
+        println!("{}", "  ⚠️   WRITE COMPLETE — BUT VERIFICATION FAILED       ".bright_red().bold());
+    } else {
+        println!("{}", "  🎉  ALL DONE — USB is ready to boot!               ".bright_green().bold());
+    }
+    println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_cyan());
+
+    if opts.verbose || verify_failed {
+        println!();
+        println!("{}", "📊 Summary:".bright_white().bold());
+        println!("  Image  : {}", input.display().to_string().bright_yellow());
+        println!("  Device : {}  {}", device.path.bright_cyan(), device.model.dimmed());
+        println!("  Written: {}", format_size(outcome.bytes_written).bright_green());
+        println!("  Block  : {}", format_block_size(opts.block_size).bright_white());
+        if let Some(range) = opts.range {
+            println!("  Range  : {}", range.describe().bright_white());
+        }
+        println!("  Time   : {}", format_eta(outcome.duration).bright_white());
+        if let Some(speed) = outcome.throughput {
+            println!("  Speed  : {}", format!("{}/s average", format_size(speed)).bright_white());
+        }
+        if let Some(rate) = opts.rate_limit {
+            println!("  Limit  : {}", format!("{}/s (pv)", format_size(rate)).bright_white());
+        }
+        println!("  Sync   : {}", if outcome.synced { "✅ done".green().to_string() } else { "failed".yellow().to_string() });
+        if let Some(extra) = opts.extra_partition {
+            let label = outcome.data_label.as_ref().map(|l| format!(", label {}", l)).unwrap_or_default();
+            println!("  Extra  : {}{}", extra.describe().bright_white(), label.bright_white());
+        }
+        if let Some(ok) = outcome.boot_ok {
+            println!("  Boot   : {}", if ok {
+                "✅ signature found".green().to_string()
+            } else {
+                "no MBR/GPT signature".yellow().to_string()
+            });
+        }
+        println!("  Verify : {}", match outcome.verify_result {
+            Some(true) if opts.verify_bytes.is_some_and(|n| n < outcome.bytes_written) => format!(
+                "✅ PARTIAL — first {} only",
+                format_size(opts.verify_bytes.unwrap_or_default())
+            ).green().to_string(),
+            Some(true) => "✅ PASSED".green().to_string(),
+            Some(false) => "❌ FAILED".red().bold().to_string(),
+            None => "skipped".dimmed().to_string(),
+        });
+        println!("  Eject  : {}", match outcome.ejected {
+            Some(true) => "✅ powered off".green().to_string(),
+            Some(false) => "failed — remove manually".yellow().to_string(),
+            None => "skipped".dimmed().to_string(),
+        });
+    }
+}
+
+/// Power off the drive via udisksctl, falling back to the sysfs delete node
+fn eject_device(device: &UsbDevice) -> Result<()> {
+    if let Some(udisksctl) = find_tool("udisksctl") {
+        log::info!("Running: udisksctl power-off -b {}", device.path);
+        let status = Command::new(udisksctl)
+            .args(["power-off", "-b", &device.path])
+            .stdout(Stdio::null())
+            .status()
+            .context("Failed to run udisksctl")?;
+        if status.success() {
+            return Ok(());
+        }
+        log::warn!("udisksctl power-off exited with {:?}", status.code());
+    }
+
+    let delete = format!("/sys/block/{}/device/delete", device.name);
+    log::info!("Writing 1 to {}", delete);
+    fs::write(&delete, "1")
+        .with_context(|| format!("udisksctl unavailable and cannot write {}", delete))
+}
+
+/// Smoothed throughput over a rolling window of (time, bytes) samples.
+///
+/// Flash drives stall while flushing their write cache, so the instantaneous
+/// rate swings wildly; averaging over a window keeps the ETA steady.
+struct RateEstimator {
+    window: Duration,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateEstimator {
+    fn new(window: Duration) -> Self {
+        RateEstimator { window, samples: VecDeque::new() }
+    }
+
+    fn push(&mut self, at: Instant, bytes: u64) {
+        self.samples.push_back((at, bytes));
+        // Drop samples older than the window, but always keep two for a rate
+        while self.samples.len() > 2 {
+            let (oldest, _) = self.samples[0];
+            if at.duration_since(oldest) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Average bytes/second across the window
+    fn rate(&self) -> Option<f64> {
+        let (t0, b0) = *self.samples.front()?;
+        let (t1, b1) = *self.samples.back()?;
+        let secs = t1.duration_since(t0).as_secs_f64();
+        if secs <= 0.0 || b1 < b0 {
+            return None;
+        }
+        Some((b1 - b0) as f64 / secs)
+    }
+
+    fn eta(&self, remaining: u64) -> Option<Duration> {
+        let r = self.rate()?;
+        if r <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(remaining as f64 / r))
+    }
+}
+
+/// 95s → "1m 35s"
+fn format_eta(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// The engine's `Progress` callbacks → an indicatif bar plus `--progress-fd` events
+struct BarProgress {
+    pb: ProgressBar,
+    /// `--progress-fd` phase: "write" or "verify"
+    phase: &'static str,
+    total: AtomicU64,
+    rate: Mutex<RateEstimator>,
+    /// The engine reports dd starved for image data
+    source_wait: AtomicBool,
+}
+
+impl BarProgress {
+    fn new(pb: ProgressBar, phase: &'static str) -> Arc<Self> {
+        Arc::new(BarProgress {
+            pb,
+            phase,
+            total: AtomicU64::new(0),
+            rate: Mutex::new(RateEstimator::new(Duration::from_secs(10))),
+            source_wait: AtomicBool::new(false),
+        })
+    }
+}
+
+impl Progress for BarProgress {
+    fn start(&self, total: u64) {
+        self.total.store(total, Ordering::SeqCst);
+        // 0 = unknown (a stream): the spinner stays a spinner
+        if total > 0 {
+            self.pb.set_length(total);
+        }
+        self.pb.set_position(0);
+        progress_event(self.phase, &[("bytes", Some(0)), ("total", Some(total))]);
+    }
+
+    fn bytes(&self, b: u64) {
+        let total = self.total.load(Ordering::SeqCst);
+        self.pb.set_position(b);
+        if self.phase != "write" {
+            progress_event(self.phase, &[("bytes", Some(b)), ("total", Some(total))]);
+            return;
+        }
+
+        let Ok(mut rate) = self.rate.lock() else { return };
+        rate.push(Instant::now(), b);
+        progress_event("write", &[
+            ("bytes", Some(b)),
+            ("total", Some(total)),
+            ("rate_bps", rate.rate().map(|r| r as u64)),
+            ("eta_s", rate.eta(total.saturating_sub(b)).map(|d| d.as_secs())),
+        ]);
+        let speed = rate
+            .rate()
+            .map(|r| format!("{}/s", format_size(r as u64)))
+            .unwrap_or_else(|| "…".into());
+        if total == 0 {
+            self.pb.set_message(format!("{}{}  {} written", icon("⚡ ", ""), speed, format_size(b)));
+            return;
+        }
+        let eta = rate
+            .eta(total.saturating_sub(b))
+            .map(format_eta)
+            .unwrap_or_else(|| "…".into());
+        let waiting = if self.source_wait.load(Ordering::SeqCst) {
+            format!("{}waiting on source read…  ", icon("⏳ ", ""))
+        } else {
+            String::new()
+        };
+        self.pb.set_message(format!(
+            "{}{}{}  {}ETA {}  {} written",
+            waiting, icon("⚡ ", ""), speed, icon("🕐 ", ""), eta, format_size(b)
+        ));
+    }
+    fn waiting_on_source(&self, waiting: bool) {
+        self.source_wait.store(waiting, Ordering::SeqCst);
+        progress_event(self.phase, &[("source_wait", Some(waiting as u64))]);
+        if waiting {
+            self.pb.set_message(format!("{}waiting on source read…", icon("⏳ ", "")));
+        }
+    }
+}
+
+// ─────────────────────────────────────────────
+//  EXTRA PARTITION
+// ─────────────────────────────────────────────
+
+/// Look up an executable on PATH
+fn find_tool(name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|p| p.is_file())
+}
+
+/// Partition names the kernel currently exposes for a disk (sdb1, sdb2, …)
+fn list_partitions(device: &UsbDevice) -> Vec<String> {
+    let mut parts: Vec<String> = fs::read_dir(format!("/sys/block/{}", device.name))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|n| n.starts_with(&device.name))
+                .filter(|n| {
+                    std::path::Path::new(&format!("/sys/block/{}/{}/partition", device.name, n))
+                        .exists()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    parts.sort();
+    parts
+}
+
+/// Add a data partition after the written image and format it exFAT (or FAT32)
+/// → the label the new filesystem got, if one was asked for and applied
+fn create_extra_partition(
+    device: &UsbDevice,
+    iso_bytes: u64,
+    extra: ExtraPartition,
+    label: Option<&str>,
+) -> Result<Option<String>> {
+    const ALIGN: u64 = 1024 * 1024;
+
+    let parted = find_tool("parted")
+        .ok_or_else(|| anyhow!("parted not found — install it to create partitions"))?;
+
+    let start = iso_bytes.div_ceil(ALIGN) * ALIGN;
+    if start + ALIGN >= device.size {
+        return Err(anyhow!("no free space left after the image"));
+    }
+    let end = match extra {
+        ExtraPartition::Rest => device.size - 1,
+        ExtraPartition::Size(b) => {
+            if start + b > device.size {
+                return Err(anyhow!(
+                    "{} does not fit — only {} free after the image",
+                    extra.describe(),
+                    format_size(device.size - start)
+                ));
+            }
+            start + b - 1
+        }
+    };
+
+    let before = list_partitions(device);
+
+    info(&format!(
+        "Creating partition {} → {} on {}",
+        format_size(start),
+        format_size(end + 1),
+        device.path
+    ));
+    let args = [
+        "-s".to_string(),
+        device.path.clone(),
+        "unit".into(),
+        "B".into(),
+        "mkpart".into(),
+        "primary".into(),
+        format!("{}B", start),
+        format!("{}B", end),
+    ];
+    log::info!("Running: parted {}", args.join(" "));
+    let status = Command::new(&parted)
+        .args(&args)
+        .status()
+        .context("Failed to run parted")?;
+    if !status.success() {
+        return Err(anyhow!("parted failed (exit code {})", status.code().unwrap_or(-1)));
+    }
+
+    // Let the kernel and udev pick up the new table
+    if let Some(partprobe) = find_tool("partprobe") {
+        let _ = Command::new(partprobe).arg(&device.path).status();
+    }
+    if let Some(udevadm) = find_tool("udevadm") {
+        let _ = Command::new(udevadm).arg("settle").status();
+    }
+
+    let new_part = list_partitions(device)
+        .into_iter()
+        .find(|p| !before.contains(p))
+        .ok_or_else(|| anyhow!("partition created but not visible yet — run partprobe and format it manually"))?;
+    let part_path = format!("/dev/{}", new_part);
+
+    // FAT stores labels upper-case; mkfs.vfat would only warn and do it anyway
+    let exfat = find_tool("mkfs.exfat").is_some();
+    let (mkfs, mut mkfs_args): (&str, Vec<String>) = if exfat {
+        ("mkfs.exfat", vec![])
+    } else if find_tool("mkfs.vfat").is_some() {
+        ("mkfs.vfat", vec!["-F".into(), "32".into()])
+    } else {
+        warn(&format!("No mkfs.exfat / mkfs.vfat found — {} left unformatted", part_path));
+        return Ok(None);
+    };
+    let label = label.map(|l| if exfat { l.to_string() } else { l.to_ascii_uppercase() });
+    if let (Some(l), false) = (&label, exfat) {
+        mkfs_args.extend(["-n".into(), l.clone()]);
+    }
+
+    info(&format!("Formatting {} with {}…", part_path, mkfs));
+    log::info!("Running: {} {} {}", mkfs, mkfs_args.join(" "), part_path);
+    let status = Command::new(mkfs)
+        .args(mkfs_args)
+        .arg(&part_path)
+        .stdout(Stdio::null())
+        .status()
+        .with_context(|| format!("Failed to run {}", mkfs))?;
+    if !status.success() {
+        return Err(anyhow!("{} failed (exit code {})", mkfs, status.code().unwrap_or(-1)));
+    }
+
+    // mkfs.exfat spells the label option -n or -L depending on the package;
+    // exfatlabel is the same in both
+    let label = match label {
+        Some(l) if exfat => match find_tool("exfatlabel") {
+            Some(tool) => {
+                log::info!("Running: exfatlabel {} {}", part_path, l);
+                let ok = Command::new(tool)
+                    .args([part_path.as_str(), l.as_str()])
+                    .stdout(Stdio::null())
+                    .status()
+                    .is_ok_and(|s| s.success());
+                if !ok {
+                    warn(&format!("exfatlabel failed — {} has no label", part_path));
+                }
+                ok.then_some(l)
+            }
+            None => {
+                warn(&format!("exfatlabel not found — {} has no label", part_path));
+                None
+            }
+        },
+        other => other,
+    };
+
+    match &label {
+        Some(l) => success(&format!("Data partition ready: {} (label {})", part_path, l)),
+        None => success(&format!("Data partition ready: {}", part_path)),
+    }
+    Ok(label)
+}
+
+// ─────────────────────────────────────────────
+//  VERIFY — hash ISO vs USB
+// ─────────────────────────────────────────────
+
+/// `written` — the write just done, if any: its byte count is the exact logical
+/// (decompressed) image length, and with --verify-streaming it carries the
+/// image hash. Without it the length is measured from the image.
+fn do_verify(
+    input: &PathBuf,
+    device: &UsbDevice,
+    (n, t): (u8, u8),
+    written: Option<&engine::WriteOutcome>,
+    opts: &WriteOptions,
+    multi: &MultiProgress,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    summarize(|s| s.target(input, device));
+    gap(multi);
+    step(n, t, &format!(
+        "Verifying {}  vs  {}",
+        input.file_name().unwrap_or_default().to_string_lossy().bright_yellow(),
+        device.path.bright_cyan()
+    ));
+
+    // The device holds the decompressed bytes, so that's what gets read back
+    let iso_bytes = match written {
+        Some(w) => w.bytes_written,
+        None => logical_size(input)?,
+    };
+    if iso_bytes == 0 {
+        return Err(anyhow!("{} is empty — there is nothing to compare", input.display()));
+    }
+    let mut verify_bytes = verify_len(iso_bytes, device.size);
+    let clamped = verify_bytes < iso_bytes;
+    if clamped {
+        warn(&format!(
+            "Image is larger than the device — only the first {} of {} can be verified",
+            format_size(verify_bytes),
+            format_size(iso_bytes)
+        ));
+    }
+    // --verify-bytes: a deliberate partial check, never to be taken for a full one
+    let partial = opts.verify_bytes.filter(|&n| n < verify_bytes);
+    if let Some(n) = partial {
+        verify_bytes = n;
+        warn(&format!(
+            "PARTIAL verify — only the first {} of {} will be compared",
+            format_size(n),
+            format_size(iso_bytes)
+        ));
+    }
+
+    if opts.dry_run {
+        let algo = opts.hash.describe();
+        info(&format!("Would read {} of {} and hash it ({})", format_size(verify_bytes), input.display(), algo));
+        info(&format!("Would read {} of {} and hash it ({})", format_size(verify_bytes), device.path, algo));
+        if opts.checkpoint.is_some() {
+            info(&format!("Would log per-region hashes every {}", format_block_size(CHECKPOINT_CHUNK)));
+        }
+        if let Some(path) = &opts.write_checksum {
+            info(&format!("Would save the image {} to {}", algo, path.display()));
+        }
+        success("DRY-RUN complete — nothing was read from the USB");
+        return Ok(());
+    }
+
+    // An assumed size or a misreporting bridge would have dd run off the end
+    let readable = device_real_size(device)?;
+    if readable < verify_bytes {
+        return Err(anyhow!(
+            "{} is smaller than the image ({} readable, {} to compare); verification impossible",
+            device.path,
+            format_size(readable),
+            format_size(verify_bytes)
+        ));
+    }
+
+    // A streamed hash is an MD5 of the whole ISO, so it's no use when clamped
+    // or partial (or when per-region hashes are wanted)
+    let known = written
+        .and_then(|w| w.source_md5.as_deref())
+        .filter(|_| !clamped && partial.is_none() && opts.hash == HashKind::Md5 && opts.checkpoint.is_none());
+    let (iso, usb) = readback_hash(input, device, verify_bytes, known, opts, multi, running)?;
+    let (iso_hash, usb_hash) = (&iso.hash, &usb.hash);
+
+    let algo = opts.hash.describe();
+    above(multi, format!("  🔐 Image {:7} : {}", algo, iso_hash.bright_yellow()));
+    above(multi, format!("  🔐 USB   {:7} : {}", algo, usb_hash.bright_cyan()));
+    gap(multi);
+
+    log::info!("ISO {} {}  USB {} {}", algo, iso_hash, algo, usb_hash);
+    summarize(|s| {
+        s.image_hash = Some((algo, iso_hash.clone()));
+        s.verified = Some(iso_hash == usb_hash);
+    });
+
+    if let Some(path) = &opts.checkpoint {
+        let bad = write_checkpoints(path, &iso, &usb, verify_bytes, algo)?;
+        report_checkpoints(path, &bad, iso.chunks.len(), verify_bytes);
+    }
+
+    if iso_hash == usb_hash {
+        match partial {
+            Some(n) => success(&format!(
+                "✅ PARTIAL verification passed — the first {} match (the remaining {} were NOT checked)",
+                format_size(n),
+                format_size(iso_bytes - n)
+            )),
+            None => success("✅ Verification PASSED — USB is a perfect copy of the image!"),
+        }
+        if let Some(path) = &opts.write_checksum {
+            if clamped || partial.is_some() {
+                warn("Checksum file not written — only part of the image was hashed");
+            } else {
+                save_checksum(path, input, iso_hash)?;
+                info(&format!("{} saved to {}", algo, path.display()));
+            }
+        }
+        Ok(())
+    } else {
+        err_msg("❌ Verification FAILED — checksums do NOT match!");
+        if opts.diagnose_mismatch {
+            diagnose_mismatch(input, device, &iso, &usb, verify_bytes, multi);
+        } else {
+            info("Re-run with --diagnose-mismatch to find the first byte that differs");
+        }
+        Err(BurnError::VerifyMismatch { iso_hash: iso_hash.clone(), device_hash: usb_hash.clone() }.into())
+    }
+}
+
+/// `--write-checksum` without `--force` won't touch an existing file
+fn check_checksum_file(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        return Err(anyhow!("{} already exists — pass --force to append to it", path.display()));
+    }
+    Ok(())
+}
+
+/// Append "<hash>  <name>" — the md5sum/sha256sum format. A compressed image
+/// is hashed decompressed, so the line names the decompressed file.
+fn save_checksum(path: &Path, input: &Path, hash: &str) -> Result<()> {
+    use std::io::Write;
+    let name = match compression_tool(input) {
+        Some(_) => input.file_stem(),
+        None => input.file_name(),
+    };
+    let name = name.map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "-".to_string());
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| writeln!(f, "{}  {}", hash, name))
+        .with_context(|| format!("Cannot write checksum file {}", path.display()))
+}
+
+/// Hash (`opts.hash`) the first `verify_bytes` of the ISO and of the device
+/// → (iso, usb), per region too with `--checkpoint`. Both run at once on their
+/// own bars — they only meet at the comparison. A `known_iso_hash` skips
+/// re-reading the ISO; a failing device read is retried `opts.verify_retries`
+/// times before giving up.
+fn readback_hash(
+    input: &Path,
+    device: &UsbDevice,
+    verify_bytes: u64,
+    known_iso_hash: Option<&str>,
+    opts: &WriteOptions,
+    multi: &MultiProgress,
+    running: Arc<AtomicBool>,
+) -> Result<(ReadHashes, ReadHashes)> {
+    let algo = opts.hash.describe();
+    let chunk = opts.checkpoint.as_ref().map(|_| CHECKPOINT_CHUNK);
+    match known_iso_hash {
+        Some(h) => info(&format!("Image {} (hashed during write): {}", algo, h.bright_yellow())),
+        None => info("Hashing the image and reading back from USB…"),
+    }
+
+    let (iso, usb) = thread::scope(|scope| {
+        // ── Hash of ISO ───────────────────────────
+        let iso = scope.spawn(|| -> Result<ReadHashes> {
+            if let Some(h) = known_iso_hash {
+                return Ok(ReadHashes { hash: h.to_string(), ..Default::default() });
+            }
+            image_hash_bar(input, "Image", verify_bytes, chunk, opts.hash, multi)
+        });
+
+        // ── Hash of USB (read exact ISO size) ─────
+        let usb = device_readback(device, verify_bytes, chunk, opts, multi, running);
+
+        let iso = iso.join().map_err(|_| anyhow!("Image hashing thread panicked"))?;
+        // Compare only when both sides completed
+        Ok::<_, anyhow::Error>((iso?, usb?))
+    })?;
+    gap(multi);
+
+    Ok((iso, usb))
+}
+
+/// `image_hash` of the first `bytes` of `input` behind its own bar, labelled `label`
+fn image_hash_bar(
+    input: &Path,
+    label: &str,
+    bytes: u64,
+    chunk: Option<u64>,
+    kind: HashKind,
+    multi: &MultiProgress,
+) -> Result<ReadHashes> {
+    let algo = kind.describe();
+    let pb = add_bar(multi, ProgressBar::new(bytes));
+    pb.set_style(verify_bar_style());
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb.set_message(format!("{} {}…", label, algo));
+    match image_hash(input, kind, bytes, chunk, BarProgress::new(pb.clone(), "hash").as_ref()) {
+        Ok(h) => {
+            pb.finish_with_message(format!("{} {}: {}", label, algo, h.hash.bright_yellow()));
+            Ok(h)
+        }
+        Err(e) => {
+            pb.abandon_with_message(format!("❌ {} hash failed", label.to_lowercase()).red().to_string());
+            Err(e.into())
+        }
+    }
+}
+
+/// `burn verify --against`: two image files hashed side by side, the same way
+/// a verify hashes the image. On a mismatch the per-region hashes say where to
+/// look and that one region is compared byte by byte.
+fn do_compare(a: &PathBuf, b: &PathBuf, opts: &WriteOptions, multi: &MultiProgress) -> Result<()> {
+    gap(multi);
+    step(1, 1, &format!(
+        "Comparing {}  vs  {}",
+        a.display().to_string().bright_yellow(),
+        b.display().to_string().bright_yellow()
+    ));
+    let (a_len, b_len) = (logical_size(a)?, logical_size(b)?);
+    if a_len != b_len {
+        warn(&format!(
+            "Sizes differ: {} is {}, {} is {} — comparing the first {}",
+            a.display(), format_size(a_len), b.display(), format_size(b_len), format_size(a_len.min(b_len))
+        ));
+    }
+    let mut len = a_len.min(b_len);
+    if let Some(n) = opts.verify_bytes.filter(|&n| n < len) {
+        warn(&format!("PARTIAL compare — only the first {} are hashed", format_size(n)));
+        len = n;
+    }
+    let algo = opts.hash.describe();
+    if opts.dry_run {
+        info(&format!("Would hash {} of {} and of {} ({})", format_size(len), a.display(), b.display(), algo));
+        success("DRY-RUN complete — nothing was read");
+        return Ok(());
+    }
+
+    let (ha, hb) = thread::scope(|scope| {
+        let ha = scope.spawn(|| image_hash_bar(a, "Image A", len, Some(COMPARE_CHUNK), opts.hash, multi));
+        let hb = image_hash_bar(b, "Image B", len, Some(COMPARE_CHUNK), opts.hash, multi);
+        let ha = ha.join().map_err(|_| anyhow!("Image hashing thread panicked"))?;
+        Ok::<_, anyhow::Error>((ha?, hb?))
+    })?;
+    gap(multi);
+    above(multi, format!("  🔐 A {:7} : {}", algo, ha.hash.bright_yellow()));
+    above(multi, format!("  🔐 B {:7} : {}", algo, hb.hash.bright_yellow()));
+    gap(multi);
+    log::info!("compare {} {} {}  {} {} {}", a.display(), algo, ha.hash, b.display(), algo, hb.hash);
+
+    if ha.hash == hb.hash {
+        if a_len != b_len {
+            err_msg(&format!("❌ The images differ in length — the first {} match", format_size(len)));
+            return Err(anyhow!("The images differ in size ({} vs {} bytes)", a_len, b_len));
+        }
+        match len < a_len {
+            true => success(&format!("✅ The first {} match (the rest was NOT compared)", format_size(len))),
+            false => success("✅ The images are identical"),
+        }
+        return Ok(());
+    }
+
+    err_msg(&format!("❌ The images differ — their {} do NOT match", algo));
+    if let Some(i) = ha.chunks.iter().zip(&hb.chunks).position(|(x, y)| x != y) {
+        let from = i as u64 * COMPARE_CHUNK;
+        info(&format!("Locating the first difference in bytes {}–{}…", from, (from + COMPARE_CHUNK).min(len) - 1));
+        match engine::first_difference(a, b, from, COMPARE_CHUNK.min(len - from)) {
+            Ok(Some(offset)) => {
+                warn(&format!("First difference at byte {} (0x{:x})", offset, offset));
+                if opts.diagnose_mismatch {
+                    show_difference((a, "First"), (b, "Second"), offset, len, multi);
+                }
+            }
+            Ok(None) => warn(&format!("Region {} differs, but its bytes read back equal — was a file changed meanwhile?", i)),
+            Err(e) => warn(&format!("Could not locate the first difference: {:#}", e)),
+        }
+    }
+    Err(BurnError::VerifyMismatch { iso_hash: ha.hash, device_hash: hb.hash }.into())
+}
+
+/// Bytes shown either side of the row holding the first difference
+const DIAGNOSE_CONTEXT: u64 = 32;
+
+/// `--diagnose-mismatch`: stream image and USB side by side up to the first
+/// differing byte (only through the first bad region with --checkpoint),
+/// show both around it and say what its position suggests
+fn diagnose_mismatch(
+    input: &Path,
+    device: &UsbDevice,
+    iso: &ReadHashes,
+    usb: &ReadHashes,
+    len: u64,
+    multi: &MultiProgress,
+) {
+    let (from, span) = match iso.chunks.iter().zip(&usb.chunks).position(|(x, y)| x != y) {
+        Some(i) => {
+            let from = i as u64 * CHECKPOINT_CHUNK;
+            (from, CHECKPOINT_CHUNK.min(len - from))
+        }
+        None => (0, len),
+    };
+    let dev = Path::new(&device.path);
+    let found = with_spinner(
+        multi,
+        &format!("Comparing {} byte by byte…", format_size(span)),
+        "✅ Compared",
+        |_| Ok(engine::first_difference(input, dev, from, span)?),
+    );
+    let offset = match found {
+        Ok(Some(offset)) => offset,
+        Ok(None) => {
+            warn("Image and USB now read back equal — is the stick returning unstable data?");
+            return;
+        }
+        Err(e) => {
+            warn(&format!("Could not locate the first difference: {:#}", e));
+            return;
+        }
+    };
+    warn(&format!(
+        "First difference at byte {} (0x{:x}) — {:.1}% into the image",
+        offset, offset, offset as f64 * 100.0 / len as f64
+    ));
+    show_difference((input, "Image"), (dev, "USB"), offset, len, multi);
+
+    let hint = if offset < 1 << 20 {
+        "It starts right at the beginning — the write likely never landed (or the stick was rewritten since)"
+    } else if offset.is_power_of_two() {
+        "It starts exactly at a power of two — typical of a stick that fakes a larger capacity"
+    } else if offset >= len / 10 * 9 {
+        "Everything before it is intact — a late failure points at bad or undersized media"
+    } else {
+        "Everything before it is intact — suspect failing flash or a flaky USB connection"
+    };
+    info(hint);
+}
+
+/// Hexdump of both sides around `offset`, differing bytes highlighted
+fn show_difference(a: (&Path, &str), b: (&Path, &str), offset: u64, len: u64, multi: &MultiProgress) {
+    let row = offset & !15;
+    let start = row.saturating_sub(DIAGNOSE_CONTEXT);
+    let count = (row + 16 + DIAGNOSE_CONTEXT).min(len) - start;
+    let (bytes_a, bytes_b) = match (engine::read_range(a.0, start, count), engine::read_range(b.0, start, count)) {
+        (Ok(x), Ok(y)) => (x, y),
+        (Err(e), _) | (_, Err(e)) => {
+            warn(&format!("Could not read the bytes around the difference: {:#}", e));
+            return;
+        }
+    };
+    let hex = |mine: &[u8], theirs: &[u8]| -> String {
+        mine.iter()
+            .enumerate()
+            .map(|(i, byte)| {
+                let cell = format!("{:02x}", byte);
+                match theirs.get(i) == Some(byte) {
+                    true => cell,
+                    false => cell.bright_red().bold().to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    for (n, (ra, rb)) in bytes_a.chunks(16).zip(bytes_b.chunks(16)).enumerate() {
+        let at = start + n as u64 * 16;
+        above(multi, format!("  {:6} {:08x}  {}", a.1, at, hex(ra, rb)));
+        above(multi, format!("  {:6} {:08x}  {}", b.1, at, hex(rb, ra)));
+    }
+}
+
+/// `--checkpoint`: one JSON line per region, then a summary line → the
+/// indexes of the regions that differ
+fn write_checkpoints(
+    path: &Path,
+    iso: &ReadHashes,
+    usb: &ReadHashes,
+    verify_bytes: u64,
+    algo: &str,
+) -> Result<Vec<usize>> {
+    let mut out = String::new();
+    let mut bad = Vec::new();
+    for (i, (a, b)) in iso.chunks.iter().zip(&usb.chunks).enumerate() {
+        let offset = i as u64 * CHECKPOINT_CHUNK;
+        let ok = a == b;
+        if !ok {
+            bad.push(i);
+        }
+        out.push_str(&format!(
+            "{{\"chunk\":{},\"offset\":{},\"len\":{},\"image\":{},\"usb\":{},\"ok\":{}}}\n",
+            i, offset, CHECKPOINT_CHUNK.min(verify_bytes - offset), json_str(a), json_str(b), ok
+        ));
+    }
+    out.push_str(&format!(
+        "{{\"summary\":true,\"hash\":{},\"bytes\":{},\"chunk_size\":{},\"chunks\":{},\"mismatched\":[{}],\"passed\":{}}}\n",
+        json_str(algo),
+        verify_bytes,
+        CHECKPOINT_CHUNK,
+        iso.chunks.len(),
+        bad.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(","),
+        iso.hash == usb.hash
+    ));
+    fs::write(path, out).with_context(|| format!("Cannot write checkpoint log {}", path.display()))?;
+    Ok(bad)
+}
+
+/// Where the USB differs, as regions: "chunks 0–13 OK, chunk 14 differs at …"
+fn report_checkpoints(path: &Path, bad: &[usize], chunks: usize, verify_bytes: u64) {
+    if bad.is_empty() {
+        info(&format!("Checkpoints: all {} regions match → {}", chunks, path.display()));
+        return;
+    }
+    warn(&format!(
+        "Checkpoints: {} of {} regions differ (log: {})",
+        bad.len(), chunks, path.display()
+    ));
+    let mut from = 0;
+    for (shown, &i) in bad.iter().enumerate() {
+        if shown == 5 {
+            warn(&format!("  … and {} more", bad.len() - shown));
+            break;
+        }
+        match i - from {
+            0 => {}
+            1 => info(&format!("  region {} OK", from)),
+            _ => info(&format!("  regions {}–{} OK", from, i - 1)),
+        }
+        let offset = i as u64 * CHECKPOINT_CHUNK;
+        let end = (offset + CHECKPOINT_CHUNK).min(verify_bytes);
+        warn(&format!("  region {} differs — bytes {}–{}", i, offset, end - 1));
+        from = i + 1;
+    }
+}
+
+/// Hash of the first `verify_bytes` of the device behind a bar, with retries
+fn device_readback(
+    device: &UsbDevice,
+    verify_bytes: u64,
+    chunk: Option<u64>,
+    opts: &WriteOptions,
+    multi: &MultiProgress,
+    running: Arc<AtomicBool>,
+) -> Result<ReadHashes> {
+    let pb = add_bar(multi, ProgressBar::new(verify_bytes));
+    pb.set_style(verify_bar_style());
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb.set_message("Reading USB…");
+    let progress = BarProgress::new(pb.clone(), "verify");
+
+    let mut attempt = 0;
+    let usb_hash = loop {
+        match device_hash(device, opts.hash, verify_bytes, opts.block_size, chunk, progress.as_ref(), running.clone()) {
+            Ok(h) => break h,
+            Err(e) if attempt < opts.verify_retries && running.load(Ordering::SeqCst) => {
+                attempt += 1;
+                multi.suspend(|| warn(&format!("{:#} — retrying ({}/{})…", e, attempt, opts.verify_retries)));
+                thread::sleep(Duration::from_secs(1));
+            }
+            Err(e) => {
+                pb.abandon_with_message("❌ read failed".red().to_string());
+                return Err(anyhow!(
+                    "Could not read {} back after {} attempt(s): {:#}.\n\
+                     This is a read failure, not a data mismatch — reconnect the USB and verify again.",
+                    device.path, attempt + 1, e
+                ));
+            }
+        }
+    };
+
+    pb.set_position(verify_bytes);
+    pb.finish_with_message("USB read done");
+    Ok(usb_hash)
+}
+
+/// One dd run behind its own bar
+fn write_pass(
+    engine_opts: engine::WriteOptions,
+    write_bytes: u64,
+    multi: &MultiProgress,
+) -> Result<engine::WriteOutcome> {
+    // Unknown length (a stream): a spinner with the byte count instead of a bar
+    let pb = match write_bytes {
+        0 => add_bar(multi, ProgressBar::new_spinner()).with_style(spinner_style()),
+        n => add_bar(multi, ProgressBar::new(n)).with_style(write_bar_style()),
+    };
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb.set_message("Starting dd…");
+
+    let running = engine_opts.running.clone();
+    let written = engine::write_iso(engine::WriteOptions {
+        progress: BarProgress::new(pb.clone(), "write"),
+        ..engine_opts
+    });
+    match &written {
+        Ok(_) => pb.finish_with_message(format!("{}", "🔥 Write complete!".red().bold())),
+        Err(_) if running.load(Ordering::SeqCst) => pb.abandon_with_message("❌ write failed".red().to_string()),
+        Err(_) => pb.abandon_with_message("⚠️  interrupted".yellow().to_string()),
+    }
+    if running.load(Ordering::SeqCst) {
+        gap(multi);
+    }
+    Ok(written?)
+}
+
+/// dd itself hit EIO on the device (not a missing device, a full one,
+/// a permission problem, a stall or an interrupt)
+fn is_io_failure(e: &anyhow::Error) -> bool {
+    e.chain()
+        .find_map(|c| c.downcast_ref::<engine::DdError>())
+        .is_some_and(|d| d.is_io_error())
+}
+
+/// dd got EINVAL with oflag=direct: O_DIRECT isn't supported there
+fn is_direct_refused(e: &anyhow::Error) -> bool {
+    e.chain()
+        .find_map(|c| c.downcast_ref::<engine::DdError>())
+        .is_some_and(|d| d.is_invalid_argument())
+}
+
+/// `--direct`: O_DIRECT transfers must start and end on sector boundaries.
+/// dd aligns its own buffer; the block size and the range are ours to check.
+fn check_direct(device: &UsbDevice, opts: &WriteOptions) -> Result<()> {
+    let sector = device.logical_sector_size.max(512);
+    if !opts.block_size.is_multiple_of(sector) {
+        return Err(anyhow!(
+            "--direct: block size {} is not a multiple of the {}-byte sectors of {}",
+            format_block_size(opts.block_size), sector, device.path
+        ));
+    }
+    if let Some(r) = opts.range.filter(|r| !r.offset.is_multiple_of(sector) || !r.len.is_multiple_of(sector)) {
+        return Err(anyhow!(
+            "--direct: --offset/--size ({}) must be multiples of the {}-byte sectors of {}",
+            r.describe(), sector, device.path
+        ));
+    }
+    Ok(())
+}
+
+/// True if the device already holds an exact copy of the whole ISO
+fn device_matches_iso(
+    input: &PathBuf,
+    device: &UsbDevice,
+    opts: &WriteOptions,
+    multi: &MultiProgress,
+    running: Arc<AtomicBool>,
+) -> Result<bool> {
+    let iso_bytes = logical_size(input)?;
+    if iso_bytes > device.size {
+        return Ok(false);
+    }
+    let (iso, usb) = readback_hash(input, device, iso_bytes, None, opts, multi, running)?;
+    log::info!("skip-if-verified: ISO {}  USB {}", iso.hash, usb.hash);
+    Ok(iso.hash == usb.hash)
+}
+
+// ─────────────────────────────────────────────
+//  CLONE — USB → image file
+// ─────────────────────────────────────────────
+
+fn do_clone(
+    device: &UsbDevice,
+    output: &PathBuf,
+    force: bool,
+    dry_run: bool,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    if output.exists() && !force {
+        return Err(anyhow!(
+            "{} already exists — use --force to overwrite",
+            output.display()
+        ));
+    }
+
+    let compressor = compression_tool(output);
+
+    println!();
+    step(1, 2, &format!(
+        "Cloning {}  →  {}",
+        device.path.bright_cyan(),
+        output.display().to_string().bright_yellow()
+    ));
+    info(&format!("USB  : {}  {}  {}",
+        device.path.bright_cyan(),
+        device.size_human().bright_white(),
+        device.model.yellow()
+    ));
+    if let Some(c) = compressor {
+        info(&format!("Compressing with {}", c.bright_white()));
+    }
+    println!();
+
+    if dry_run {
+        success("DRY-RUN complete — would run:");
+        match compressor {
+            Some(c) => info(&format!(
+                "dd {} bs=4M status=progress | {} -c > {}",
+                shell_quote(&format!("if={}", device.path)), c, shell_quote(&output.to_string_lossy())
+            )),
+            None => info(&format!(
+                "dd {} {} bs=4M status=progress",
+                shell_quote(&format!("if={}", device.path)),
+                shell_quote(&format!("of={}", output.display()))
+            )),
+        }
+        return Ok(());
+    }
+
+    let pb = ProgressBar::new(device.size);
+    hide_if_machine(&pb);
+    pb.set_style(verify_bar_style());
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb.set_message("Reading…");
+
+    let mut dd_args = vec![
+        format!("if={}", device.path),
+        "bs=4M".to_string(),
+        "status=progress".to_string(),
+    ];
+    if compressor.is_none() {
+        dd_args.push(format!("of={}", output.display()));
+    }
+
+    log::info!(
+        "Running: dd {}{}",
+        dd_args.iter().map(|a| shell_quote(a)).collect::<Vec<_>>().join(" "),
+        compressor.map(|c| format!(" | {} -c", c)).unwrap_or_default()
+    );
+    let mut dd = Command::new(dd_bin())
+        .args(&dd_args)
+        .stdout(if compressor.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to launch dd — is it installed?")?;
+    track_child(dd.id(), false);
+
+    let mut comp_proc = match compressor {
+        Some(c) => {
+            let out_file = fs::File::create(output)
+                .with_context(|| format!("Cannot create {}", output.display()))?;
+            Some(
+                Command::new(c)
+                    .arg("-c")
+                    .stdin(dd.stdout.take().unwrap())
+                    .stdout(out_file)
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .with_context(|| format!("Failed to launch {} — is it installed?", c))?,
+            )
+        }
+        None => None,
+    };
+
+    let stderr = dd.stderr.take().unwrap();
+    let pb2 = pb.clone();
+    let total = device.size;
+    let parse_thread = spawn_dd_progress(stderr, running, move |b| {
+        pb2.set_position(b.min(total));
+        pb2.set_message(format!("{} read", format_size(b)));
+    });
+
+    let status = dd.wait().context("dd process error")?;
+    untrack_child();
+    parse_thread.join().ok();
+    log::info!("clone dd exited with {:?}", status.code());
+
+    let comp_ok = match comp_proc.as_mut() {
+        Some(p) => p.wait().context("compressor process error")?.success(),
+        None => true,
+    };
+
+    if !status.success() || !comp_ok {
+        pb.abandon_with_message("❌ clone failed".red().to_string());
+        println!();
+        return Err(anyhow!(
+            "Clone failed (dd exit code {}{}).\n\
+             • Run with sudo or as root\n\
+             • Make sure USB is properly connected",
+            status.code().unwrap_or(-1),
+            if comp_ok { "" } else { ", compressor failed" }
+        ));
+    }
+
+    pb.set_position(device.size);
+    pb.finish_with_message(format!("{}", "💽 Clone complete!".green().bold()));
+    println!();
+
+    let _ = Command::new("sync").status();
+
+    step(2, 2, "Checksumming image…");
+    let pb = ProgressBar::new(0);
+    hide_if_machine(&pb);
+    pb.set_style(verify_bar_style());
+    pb.set_message("Computing image MD5…");
+    pb.enable_steady_tick(Duration::from_millis(120));
+    let md5 = hash_file(output, HashKind::Md5, BarProgress::new(pb.clone(), "hash").as_ref())?;
+    pb.finish_with_message(format!("Image MD5: {}", md5.bright_yellow()));
+
+    let out_bytes = fs::metadata(output)
+        .with_context(|| format!("Cannot read {}", output.display()))?
+        .len();
+
+    println!();
+    success(&format!("Image saved to {}", output.display()));
+    println!("  📦 Size : {}", format_size(out_bytes).bright_white());
+    println!("  🔐 MD5  : {}", md5.bright_yellow());
+    println!();
+
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+//  WIPE
+// ─────────────────────────────────────────────
+
+/// How much `--mode quick` clears at each end of the device
+const QUICK_WIPE_BYTES: u64 = 16 * 1024 * 1024;
+
+impl WipeMode {
+    fn describe(&self) -> &'static str {
+        match self {
+            WipeMode::Zero => "overwrite everything with zeros",
+            WipeMode::Random => "overwrite everything with random data",
+            WipeMode::Quick => "clear partition tables + filesystem headers only",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            WipeMode::Random => "/dev/urandom",
+            WipeMode::Zero | WipeMode::Quick => "/dev/zero",
+        }
+    }
+}
+
+/// dd runs for a wipe → (args, bytes). Quick mode hits the start and the
+/// end (backup GPT lives in the last sectors).
+fn wipe_passes(device: &UsbDevice, mode: WipeMode) -> Vec<(Vec<String>, u64)> {
+    let base = |bs: &str| vec![
+        format!("if={}", mode.source()),
+        format!("of={}", device.path),
+        format!("bs={}", bs),
+        "status=progress".to_string(),
+        "conv=fsync".to_string(),
+    ];
+    match mode {
+        WipeMode::Zero | WipeMode::Random => {
+            let mut args = base("4M");
+            args.extend([format!("count={}", device.size), "iflag=count_bytes,fullblock".into()]);
+            vec![(args, device.size)]
+        }
+        WipeMode::Quick => {
+            let len = QUICK_WIPE_BYTES.min(device.size);
+            let mut head = base("1M");
+            head.extend([format!("count={}", len), "iflag=count_bytes".into()]);
+            let mut tail = base("1M");
+            tail.extend([
+                format!("seek={}", device.size - len),
+                format!("count={}", len),
+                "iflag=count_bytes".into(),
+                "oflag=seek_bytes".into(),
+            ]);
+            vec![(head, len), (tail, len)]
+        }
+    }
+}
+
+fn wipe_confirm(device: &UsbDevice, mode: WipeMode, opts: &WriteOptions) -> Result<bool> {
+    check_raid_lvm(device, opts)?;
+    let theme = ColorfulTheme::default();
+    println!();
+    println!("{}", "┌─────────────────────────────────────────────────────┐".bright_red());
+    println!("{} {} {}",
+        "│".bright_red(),
+        "          🧹  WIPE — DATA WILL BE DESTROYED!  🧹         ".bright_red().bold(),
+        "│".bright_red()
+    );
+    println!("{}", "├─────────────────────────────────────────────────────┤".bright_red());
+    println!("{}  {:20} {}  {}",
+        "│".bright_red(),
+        "Target device:".bright_white(),
+        device.path.bright_red().bold(),
+        "│".bright_red()
+    );
+    println!("{}  {:20} {}  {}",
+        "│".bright_red(),
+        "Device model:".bright_white(),
+        device.model.bright_yellow(),
+        "│".bright_red()
+    );
+    println!("{}  {:20} {}  {}",
+        "│".bright_red(),
+        "Device size:".bright_white(),
+        device.size_human().bright_yellow(),
+        "│".bright_red()
+    );
+    print_device_contents(device);
+    println!("{}  {:20} {}  {}",
+        "│".bright_red(),
+        "Wipe mode:".bright_white(),
+        mode.describe().bright_yellow(),
+        "│".bright_red()
+    );
+    println!("{}", "└─────────────────────────────────────────────────────┘".bright_red());
+    println!();
+
+    if mode == WipeMode::Quick {
+        warn("Quick wipe only destroys the partition tables and filesystem metadata —");
+        warn("the rest of the old data is still on the stick and can be recovered.");
+        warn("Use --mode zero or --mode random to really erase it.");
+        println!();
+    }
+
+    let first = Confirm::with_theme(&theme)
+        .with_prompt(format!("WIPE {}? ({})", device.path, device.model))
+        .default(false)
+        .interact()?;
+    if !first {
+        warn("Cancelled.");
+        return Ok(false);
+    }
+    confirm_final(device, device.size > opts.large_device_bytes, opts)
+}
+
+fn do_wipe(
+    device: &UsbDevice,
+    mode: WipeMode,
+    opts: &WriteOptions,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    let passes = wipe_passes(device, mode);
+    let total: u64 = passes.iter().map(|(_, n)| n).sum();
+
+    println!();
+    step(1, 2, &format!("Wiping {} ({})", device.path.bright_cyan(), mode.describe()));
+
+    revalidate_device(&RealSysfs, device)?;
+
+    unmount_all(device, opts.dry_run)?;
+    if opts.dry_run {
+        success("DRY-RUN complete — would run:");
+        for (args, _) in &passes {
+            info(&format!("dd {}", args.join(" ")));
+        }
+        info("sync");
+        return Ok(());
+    }
+
+    if is_write_protected(device) {
+        return Err(anyhow!(
+            "{} appears write-protected — check the lock switch on the stick",
+            device.path
+        ));
+    }
+    println!();
+
+    let pb = ProgressBar::new(total);
+    hide_if_machine(&pb);
+    pb.set_style(write_bar_style());
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb.set_message("Wiping…");
+
+    let mut done = 0u64;
+    for (args, len) in &passes {
+        log::info!("Running: dd {}", args.join(" "));
+        let mut child = Command::new(dd_bin())
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to launch dd — is it installed?")?;
+        track_child(child.id(), true);
+
+        let pb2 = pb.clone();
+        let offset = done;
+        let parse_thread = spawn_dd_progress(child.stderr.take().unwrap(), running.clone(), move |b| {
+            pb2.set_position(offset + b);
+            progress_event("wipe", &[("bytes", Some(offset + b)), ("total", Some(total))]);
+            pb2.set_message(format!("{} wiped", format_size(offset + b)));
+        });
+
+        let status = child.wait().context("dd process error")?;
+        untrack_child();
+        parse_thread.join().ok();
+        log::info!("wipe dd exited with {:?}", status.code());
+
+        if !running.load(Ordering::SeqCst) {
+            pb.abandon_with_message("⚠️  interrupted".yellow().to_string());
+            return Err(anyhow!("Interrupted — the USB is only partly wiped"));
+        }
+        if !status.success() {
+            pb.abandon_with_message("❌ wipe failed".red().to_string());
+            println!();
+            return Err(fail(EXIT_WRITE_FAILED, anyhow!(
+                "dd failed while wiping (exit code {}).\n\
+                 • Run with sudo or as root\n\
+                 • Make sure USB is properly connected",
+                status.code().unwrap_or(-1)
+            )));
+        }
+        done += len;
+    }
+    pb.set_position(total);
+    pb.finish_with_message(format!("{}", "🧹 Wipe complete!".red().bold()));
+    println!();
+
+    step(2, 2, "Flushing buffers to USB (sync)…");
+    progress_event("sync", &[]);
+    if Command::new("sync").status().map(|s| s.success()).unwrap_or(false) {
+        success(&format!("{} wiped", device.path));
+    } else {
+        warn("sync failed — wait for drive activity to stop before removing it");
+    }
+    if mode == WipeMode::Quick {
+        info("Only metadata was cleared; old data may still be recoverable.");
+    }
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+//  LIST
+// ─────────────────────────────────────────────
+
+fn do_list(scanner: &DeviceScanner, all: bool) {
+    if all {
+        return do_list_all(scanner);
+    }
+    println!();
+    println!("{}", "📋 Removable USB drives:".bright_white().bold());
+    println!("{}", "──────────────────────────────────────────────────────".dimmed());
+
+    let devices = scanner.devices();
+    if devices.is_empty() {
+        warn("No USB drives detected.");
+        info("Plug in a USB drive and try again.");
+        info("Debug: burn doctor — shows why each disk was skipped");
+        return;
+    }
+
+    for d in devices {
+        println!("  🟢  {}  {}  {}  [transport: {}]",
+            d.path.bright_cyan().bold(),
+            d.size_human().bright_white(),
+            d.model.bright_yellow(),
+            d.transport.dimmed()
+        );
+        if let Some(mark) = protected_mark(d) {
+            let ro = if is_kernel_read_only(d) { ", read-only until replugged" } else { "" };
+            println!("      {}", format!("⚠ previously flagged as protected ({}{})", mark.name, ro).bright_red());
+        }
+    }
+    println!();
+    warn("⚠️  Writing to any of these will ERASE all data on it!");
+    println!();
+}
+
+/// `list --all`: everything the kernel sees, and why most of it is filtered out
+fn do_list_all(scanner: &DeviceScanner) {
+    println!();
+    println!("{}", "📋 All block devices (diagnostic):".bright_white().bold());
+    println!("{}", "──────────────────────────────────────────────────────".dimmed());
+
+    let safe: Vec<&str> = scanner.devices().iter().map(|d| d.path.as_str()).collect();
+    for d in detect_all_block_devices(&RealSysfs) {
+        let line = format!("{:14} {:>9}  {:8} rm={}  {}",
+            d.path,
+            d.size_human(),
+            d.transport,
+            d.removable as u8,
+            d.model
+        );
+        if safe.contains(&d.path.as_str()) {
+            println!("  🟢  {}", line.bright_cyan());
+        } else {
+            println!("  🔴  {}  {}", line.dimmed(), "NOT A SAFE TARGET".bright_red().bold());
+        }
+    }
+    println!();
+    info("Only 🟢 devices (removable, USB transport, ≥ 100 MB) can ever be selected for writing.");
+    info("The 100 MB minimum is the drive's size — smaller images are fine.");
+    if !allowed_devices().is_empty() {
+        info(&format!("Only drives in allowed_devices are offered: {}", allowed_devices().join(", ")));
+    }
+    println!();
+}
+
+// ─────────────────────────────────────────────
+//  DOCTOR — why is(n't) my USB detected?
+// ─────────────────────────────────────────────
+
+fn do_doctor() {
+    println!();
+    println!("{}", "🩺 USB detection report:".bright_white().bold());
+    println!("{}", "──────────────────────────────────────────────────────".dimmed());
+
+    let all = detect_all_block_devices(&RealSysfs);
+    if all.is_empty() {
+        warn("/sys/block is empty or unreadable — is sysfs mounted?");
+    }
+    let mut usable = 0;
+    let mut usb_not_removable = false;
+    for d in &all {
+        let line = format!("{:14} {:>9}  {}", d.path, d.size_human(), d.model);
+        match exclusion_reason(&RealSysfs, d) {
+            None => {
+                usable += 1;
+                println!("  🟢  {}  {}", line.bright_cyan(), "removable USB drive — can be selected".green());
+            }
+            Some(why @ Exclusion::Virtual) => {
+                println!("  ⚪  {}  {}", d.path.dimmed(), why.describe().dimmed());
+            }
+            Some(why) => {
+                usb_not_removable |= why == Exclusion::NotRemovable && d.transport == "usb";
+                println!("  🔴  {}  {}", line.dimmed(), why.describe().yellow());
+            }
+        }
+    }
+    println!();
+
+    if usable == 0 {
+        warn("No drive passed every check.");
+        if usb_not_removable {
+            info("A disk on the USB bus reports removable=0 (common for USB SSDs and some enclosures).");
+            info("It is skipped on purpose: burn never writes to disks that claim to be fixed.");
+        }
+        info("Re-plug the stick, try another port or cable, then check `dmesg | tail` for errors.");
+    } else {
+        success(&format!("{} drive(s) can be selected", usable));
+    }
+    println!();
+
+    println!("{}", "  lsblk -d -o NAME,TRAN,RM,SIZE,MODEL:".bright_white().bold());
+    match Command::new("lsblk").args(["-d", "-o", "NAME,TRAN,RM,SIZE,MODEL"]).output() {
+        Ok(out) => {
+            for line in String::from_utf8_lossy(&out.stdout).lines() {
+                println!("    {}", line);
+            }
+        }
+        Err(_) => println!("    {}", "(lsblk not available)".dimmed()),
+    }
+    println!();
+
+    // Only real block devices — skip proc, tmpfs, cgroup…
+    println!("{}", "  /proc/mounts (block devices):".bright_white().bold());
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let mut any = false;
+    for line in mounts.lines().filter(|l| l.starts_with("/dev/")) {
+        println!("    {}", line);
+        any = true;
+    }
+    if !any {
+        println!("    {}", "(none)".dimmed());
+    }
+    println!();
+}
+
+// ─────────────────────────────────────────────
+//  INFO
+// ─────────────────────────────────────────────
+
+fn do_info(device: &UsbDevice, json: bool) {
+    let scheme = read_partition_scheme(device)
+        .map_err(|e| log::debug!("partition table read failed: {:#}", e))
+        .ok();
+    let smart = read_smart(device);
+    if json {
+        println!("{}", info_json(device, scheme, smart.as_ref()));
+        return;
+    }
+
+    println!();
+    println!("{}", format!("📊 Device Info — {}", device.path).bright_white().bold());
+    println!("{}", "──────────────────────────────────────────────────────".dimmed());
+
+    let fields = vec![
+        ("🔌 Device",     device.path.clone()),
+        ("📦 Model",      device.model.clone()),
+        ("🏷️  Serial",     device.serial.clone().unwrap_or_else(|| "(unknown)".into())),
+        ("🔗 By-id",      device.by_id.clone().unwrap_or_else(|| "(none)".into())),
+        ("💾 Size",       format!("{}  ({}, {} bytes)",
+            device.size_human(),
+            match units() { Units::Si => Units::Iec, Units::Iec => Units::Si }.format(device.size),
+            device.size
+        )),
+        ("🔄 Removable",  device.removable.to_string()),
+        ("🚌 Transport",  device.transport.clone()),
+        ("🧱 Sector",     format!("{} bytes (logical)", device.logical_sector_size)),
+    ];
+
+    for (label, value) in &fields {
+        println!("  {:20} {}", label.bright_cyan(), value.bright_white());
+    }
+
+    // Verdict from the partition table itself; lsblk below is the detail
+    let layout = match scheme {
+        Some(scheme) if scheme.is_bootable() => scheme.describe().green().to_string(),
+        Some(scheme) => scheme.describe().yellow().to_string(),
+        None => "(unreadable — try as root)".dimmed().to_string(),
+    };
+    println!("  {:20} {}", "🥾 Boot".bright_cyan(), layout);
+    print_smart(smart.as_ref());
+
+    // lsblk for partitions
+    println!();
+    println!("{}", "  Partitions:".bright_white().bold());
+    let _ = Command::new("lsblk")
+        .args(["-o", "NAME,SIZE,FSTYPE,LABEL,MOUNTPOINT", &device.path])
+        .status();
+    println!();
+}
+
+/// SMART lines for `info`: green = fine, yellow = worth watching, red = don't trust it
+fn print_smart(smart: Option<&SmartInfo>) {
+    let Some(smart) = smart else {
+        println!("  {:20} {}", "🩺 SMART".bright_cyan(), "SMART unavailable".dimmed());
+        return;
+    };
+    let rate = |v: String, good: bool, ok: bool| {
+        if good { v.green() } else if ok { v.yellow() } else { v.red().bold() }
+    };
+    if let Some(healthy) = smart.healthy {
+        let v = if healthy { "PASSED" } else { "FAILING" }.to_string();
+        println!("  {:20} {}", "🩺 SMART".bright_cyan(), rate(v, healthy, false));
+    }
+    if let Some(h) = smart.power_on_hours {
+        println!("  {:20} {}", "⏱️  Power-on".bright_cyan(), rate(format!("{} h", h), h < 1_000, h < 10_000));
+    }
+    if let Some(left) = smart.life_left {
+        println!("  {:20} {}", "🔋 Life left".bright_cyan(), rate(format!("{}%", left), left > 50, left > 10));
+    }
+    if let Some(n) = smart.reallocated_sectors {
+        println!("  {:20} {}", "🧩 Reallocated".bright_cyan(), rate(format!("{} sectors", n), n == 0, n < 10));
+    }
+}
+
+/// `info --json`: one object, SMART values as null where unknown
+fn info_json(device: &UsbDevice, scheme: Option<PartitionScheme>, smart: Option<&SmartInfo>) -> String {
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "null".into());
+    let smart = smart.map(|s| format!(
+        "{{\"healthy\":{},\"power_on_hours\":{},\"life_left_percent\":{},\"reallocated_sectors\":{}}}",
+        opt(s.healthy.map(|v| v.to_string())),
+        opt(s.power_on_hours.map(|v| v.to_string())),
+        opt(s.life_left.map(|v| v.to_string())),
+        opt(s.reallocated_sectors.map(|v| v.to_string())),
+    ));
+    format!(
+        "{{\"device\":{},\"by_id\":{},\"model\":{},\"serial\":{},\"size\":{},\"removable\":{},\"transport\":{},\"logical_sector_size\":{},\"boot\":{},\"smart\":{}}}",
+        json_str(&device.path),
+        opt(device.by_id.as_deref().map(json_str)),
+        json_str(&device.model),
+        opt(device.serial.as_deref().map(json_str)),
+        device.size,
+        device.removable,
+        json_str(&device.transport),
+        device.logical_sector_size,
+        opt(scheme.map(|s| json_str(s.describe()))),
+        opt(smart),
+    )
+}
+
+// ─────────────────────────────────────────────
+//  WIZARD
+// ─────────────────────────────────────────────
+
+/// After a successful write the wizard offers to flash another drive; it
+/// loops back to the menu with the image remembered and a fresh device scan.
+fn do_wizard(base: &WriteOptions, running: Arc<AtomicBool>) -> Result<()> {
+    let theme = ColorfulTheme::default();
+
+    println!();
+    println!("{}", "🎛️  BurnEngine USB — Interactive Wizard".bright_cyan().bold());
+    println!("{}", "──────────────────────────────────────────".dimmed());
+    if base.dry_run {
+        warn("DRY-RUN — nothing will be written to or read from the USB");
+    }
+    println!();
+
+    let ops = vec![
+        "🔥  Write ISO / disk image to USB",
+        "🔍  Verify USB against an image",
+        "📋  List USB drives",
+        "📊  Show device info",
+    ];
+
+    let mut last_image: Option<PathBuf> = None;
+    loop {
+        // Drives come and go between rounds — never reuse the last scan
+        let scanner = scanner(base.verbose);
+
+        let op = Select::with_theme(&theme)
+            .with_prompt("What do you want to do?")
+            .items(&ops)
+            .default(0)
+            .interact()?;
+
+        match op {
+            0 => {
+                let input = pick_wizard_image(last_image.as_deref())?;
+                let device = select_usb_device(&scanner, Some(image_size(&input)?))?;
+
+                let mut opts = WriteOptions { zap: zap_by_default(&input), ..base.clone() };
+                if !safety_confirm(&input, &device, &opts)? {
+                    return Err(cancelled());
+                }
+
+                let extra = vec!["✅ Verify MD5 after write"];
+                let selected = dialoguer::MultiSelect::with_theme(&theme)
+                    .with_prompt("⚙️  Options")
+                    .items(&extra)
+                    .defaults(&[true])
+                    .interact()?;
+                opts.verify = selected.contains(&0);
+
+                println!();
+                write_and_report(&input, &device, &opts, running.clone())?;
+                last_image = Some(input);
+
+                println!();
+                let again = Confirm::with_theme(&theme)
+                    .with_prompt("Flash another drive?")
+                    .default(false)
+                    .interact()?;
+                if !again {
+                    break;
+                }
+                info("Swap in the next USB drive, then pick it from the list.");
+                println!();
+            }
+            1 => {
+                let input = pick_file()?;
+                let device = select_usb_device(&scanner, None)?;
+                do_verify(&input, &device, (1, 1), None, base, &phase_multi(), running)?;
+                break;
+            }
+            2 => {
+                do_list(&scanner, false);
+                break;
+            }
+            3 => {
+                let device = select_usb_device(&scanner, None)?;
+                do_info(&device, false);
+                break;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// Wizard rounds after the first: offer the image just written before the picker
+fn pick_wizard_image(last: Option<&Path>) -> Result<PathBuf> {
+    let Some(last) = last else { return pick_file() };
+    let same = format!("Same image ({})", last.file_name().unwrap_or_default().to_string_lossy());
+    let choice = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Image")
+        .items(&[same.as_str(), "Choose another image…"])
+        .default(0)
+        .interact()?;
+    match choice {
+        0 => Ok(last.to_path_buf()),
+        _ => pick_file(),
+    }
+}
+
+// ─────────────────────────────────────────────
+//  CTRL-C
+// ─────────────────────────────────────────────
+
+/// Bars that may be on screen, for the Ctrl-C handler (weak: a dropped bar is gone)
+static LIVE_BARS: Mutex<Vec<indicatif::WeakProgressBar>> = Mutex::new(Vec::new());
+
+/// Every bar comes through `hide_if_machine` or `add_bar`, which register it here
+fn track_bar(pb: &ProgressBar) {
+    if let Ok(mut bars) = LIVE_BARS.lock() {
+        bars.retain(|b| b.upgrade().is_some_and(|b| !b.is_finished()));
+        bars.push(pb.downgrade());
+    }
+}
+
+/// Freeze whatever bars are live where they stand and show the cursor again
+/// (indicatif and the dialoguer prompts hide it) — `process::exit` runs no
+/// destructors that would do it for us
+fn release_terminal() {
+    use std::io::{IsTerminal, Write};
+    if let Ok(bars) = LIVE_BARS.lock() {
+        for pb in bars.iter().filter_map(|b| b.upgrade()) {
+            if !pb.is_finished() {
+                pb.abandon();
+            }
+        }
+    }
+    const SHOW_CURSOR: &[u8] = b"\x1b[?25h";
+    if std::io::stderr().is_terminal() {
+        let _ = std::io::stderr().write_all(SHOW_CURSOR);
+    }
+    if std::io::stdout().is_terminal() {
+        let _ = std::io::stdout().write_all(SHOW_CURSOR);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Ctrl-C, and with ctrlc's `termination` feature also SIGTERM (systemd, a
+/// GUI launcher closing) and SIGHUP (the terminal went away): stop dd and sync.
+/// The terminal may already be gone, so nothing here may panic on a failed print.
+fn setup_ctrlc(running: Arc<AtomicBool>) {
+    ctrlc::set_handler(move || {
+        use std::io::Write;
+        let say = |line: String| {
+            let _ = writeln!(std::io::stdout(), "{}", line);
+        };
+        running.store(false, Ordering::SeqCst);
+        release_terminal();
+        say(format!("\n\n{} {}", "⚠️ ".yellow(), "Interrupt! Stopping…".red().bold()));
+
+        if stop_active_child() == Some(true) {
+            // Flush whatever dd already handed to the kernel
+            let _ = Command::new("sync").status();
+            say(format!("{} {}",
+                "⚠️ ".yellow(),
+                "The USB now holds a PARTIAL image and will not boot.".red().bold()
+            ));
+            say(format!("{} {}",
+                "⚠️ ".yellow(),
+                "Wait for drive activity to stop before removing it, then re-run the write.".yellow()
+            ));
+        } else if let Some(mounts) = UNMOUNTED.try_lock().ok().filter(|_| !DEVICE_TOUCHED.load(Ordering::SeqCst)) {
+            // Nothing was being written — the data is intact, just unmounted
+            for (dev, point) in mounts.iter() {
+                say(format!("{} {} was unmounted from {} — udisksctl mount -b {} brings it back",
+                    "⚠️ ".yellow(), dev, point, dev));
+            }
+        }
+        write_summary(1, Some(&anyhow!("Interrupted")));
+        std::process::exit(1);
+    })
+    .expect("Failed to set the signal handler");
+}
+
+// ─────────────────────────────────────────────
+//  MAIN
+// ─────────────────────────────────────────────
+
+fn main() {
+    let result = run();
+    write_summary(result.as_ref().map_or_else(exit_code, |()| 0), result.as_ref().err());
+    let Err(e) = result else { return };
+    let code = exit_code(&e);
+    log::error!("exiting with {}: {:#}", code, e);
+    // A cancel was already reported at the prompt
+    if code != EXIT_CANCELLED {
+        eprintln!("Error: {:?}", e);
+    }
+    std::process::exit(code);
+}
+
+fn run() -> Result<()> {
+    let cli = Cli::parse();
+
+    // Completion scripts go to stdout — no banner, no preflight
+    if let Commands::Completions { shell } = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "burn", &mut std::io::stdout());
+        return Ok(());
+    }
+    let no_color = cli.no_color
+        || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if no_color {
+        colored::control::set_override(false);
+        PLAIN_OUTPUT.store(true, Ordering::Relaxed);
+    }
+    if let Some(path) = &cli.log_file {
+        init_log_file(path)?;
+    }
+    if let Some(fd) = cli.progress_fd {
+        init_progress_fd(fd)?;
+    }
+    if let Some(path) = &cli.summary_json {
+        let _ = SUMMARY_PATH.set(path.clone());
+        let command = match cli.command {
+            Commands::Write { .. } => Some("write"),
+            Commands::Verify { .. } => Some("verify"),
+            _ => None,
+        };
+        summarize(|s| {
+            s.command = command;
+            s.started = Some(Instant::now());
+            s.dry_run = cli.dry_run;
+        });
+    }
+    QUIET.store(cli.quiet, Ordering::Relaxed);
+    DATA_STDOUT.store(matches!(cli.command, Commands::Info { json: true, .. }), Ordering::Relaxed);
+    {
+        use std::io::IsTerminal;
+        if !quiet() && !machine_progress() && !std::io::stdout().is_terminal() {
+            let _ = LINE_PROGRESS.set(Duration::from_secs(cli.progress_interval.max(1)));
+        }
+    }
+    set_units(cli.units);
+    let running = Arc::new(AtomicBool::new(true));
+    setup_ctrlc(running.clone());
+
+    if !quiet() && !data_stdout() {
+        print_banner();
+    }
+
+    if let Some(dd) = cli.dd_path.clone() {
+        set_dd_path(dd);
+    }
+
+    ensure_root(&cli.command, cli.force, cli.dry_run)?;
+
+    preflight_tools(&tools_for(&cli.command))?;
+    let config = Config::load()?;
+    if !config.allowed_devices.is_empty() {
+        log::info!("allowed_devices: {}", config.allowed_devices.join(", "));
+    }
+    set_allowed_devices(config.allowed_devices.clone());
+    let scanner = scanner(cli.verbose);
+
+    if cli.dry_run {
+        warn("DRY-RUN mode — nothing will be written.");
+        say(format_args!(""));
+    }
+
+    match cli.command {
+        Commands::Write {
+            image_pos,
+            device_pos,
+            input,
+            device,
+            verify,
+            verify_retries,
+            no_source_verify,
+            precheck_source,
+            verify_streaming,
+            checkpoint,
+            verify_bytes,
+            write_checksum,
+            diagnose_mismatch,
+            block_size,
+            no_retry,
+            extra_partition,
+            label,
+            eject,
+            skip_if_verified,
+            mark,
+            check_mark,
+            protect,
+            zap,
+            no_zap,
+            discard,
+            check_boot,
+            dd_conv,
+            dd_oflag,
+            direct,
+            sync_interval,
+            dd_status_none,
+            slow_threshold,
+            rate_limit,
+            read_buffer,
+            notify,
+            keep_awake,
+            confirm_string,
+            allow_raid,
+            assume_device_size,
+            after_command,
+            offset,
+            size,
+            output_image,
+            timeout,
+            target_file,
+            watch,
+            stdin,
+        } => {
+            // `burn write IMAGE DEVICE` — clap already refuses mixing them with -i/-d
+            let (input, device) = (input.or(image_pos), device.or(device_pos));
+            let input = match input {
+                _ if stdin => PathBuf::from(STDIN_IMAGE),
+                Some(p) if is_url(&p) => p,
+                Some(p) => {
+                    if !p.exists() { return Err(anyhow!("Image not found: {}", p.display())); }
+                    p
+                }
+                None => pick_file()?,
+            };
+            let streamed = is_streamed(&input);
+            let source_bytes = source_size(&input)?;
+            if let Some(path) = &write_checksum {
+                check_checksum_file(path, cli.force)?;
+            }
+
+            let device = if let Some(target) = target_file {
+                warn(&format!(
+                    "--target-file: writing to {} — a regular FILE, NOT a real USB device (testing only)",
+                    target.display()
+                ));
+                UsbDevice::file_target(&target, source_bytes.unwrap_or(0))
+            } else if watch {
+                wait_for_usb(running.clone())?
+            } else {
+                // The picker hides drives that look too small — not when the size is overridden
+                resolve_device(&scanner, device, source_bytes.filter(|_| assume_device_size.is_none()))?
+            };
+            let device = match assume_device_size {
+                Some(size) => assume_size(device, size)?,
+                None => device,
+            };
+
+            if output_image.is_some() && !cli.dry_run {
+                return Err(anyhow!("--output-image only works together with --dry-run"));
+            }
+            if streamed && (offset.is_some() || size.is_some()) {
+                return Err(anyhow!("--offset/--size need an image file — they can't be used with a streamed image"));
+            }
+
+            // A streamed image can't be read a second time: its hash is taken on
+            // the way in, and only when its length is known beforehand
+            let mut verify = verify
+                || verify_streaming
+                || checkpoint.is_some()
+                || verify_bytes.is_some()
+                || write_checksum.is_some()
+                || diagnose_mismatch;
+            if streamed && verify && source_bytes.is_none() {
+                warn("Verify skipped — the size of a streamed image isn't known up front");
+                verify = false;
+            }
+            let verify_streaming = verify_streaming || (streamed && verify);
+
+            let opts = WriteOptions {
+                verify,
+                source_verify: !no_source_verify,
+                precheck_source,
+                verify_streaming,
+                verify_retries,
+                hash: HashKind::Md5,
+                checkpoint,
+                verify_bytes,
+                write_checksum,
+                diagnose_mismatch,
+                block_size,
+                retry: !no_retry,
+                extra_partition,
+                label,
+                eject,
+                skip_if_verified,
+                mark: mark || protect,
+                check_mark,
+                protect,
+                zap: zap || (!no_zap && offset.is_none() && size.is_none() && zap_by_default(&input)),
+                discard,
+                check_boot,
+                timeout: timeout.map(Duration::from_secs),
+                sync_interval,
+                dd_status_none,
+                slow_threshold,
+                // burn has no native writer to pace itself — without pv the
+                // write simply runs at full speed
+                rate_limit: rate_limit.filter(|&r| r > 0).filter(|_| {
+                    let found = find_tool("pv").is_some();
+                    if !found {
+                        warn("--rate-limit needs pv (not installed) — writing at full speed");
+                    }
+                    found
+                }),
+                read_buffer,
+                range: WriteRange::resolve(offset, size, source_bytes.unwrap_or(0))?,
+                notify,
+                keep_awake,
+                after_command,
+                confirm_string,
+                allow_raid,
+                plan_path: output_image,
+                dd_conv,
+                dd_oflag: match direct {
+                    true => Some("direct".to_string()),
+                    false => (dd_oflag != "none").then_some(dd_oflag),
+                },
+                direct,
+                large_device_bytes: config.large_device_bytes,
+                force: cli.force,
+                dry_run: cli.dry_run,
+                verbose: cli.verbose,
+            };
+            if streamed {
+                check_streamed(&input, &opts)?;
+            }
+
+            if !safety_confirm(&input, &device, &opts)? {
+                return Err(cancelled());
+            }
+
+            write_and_report(&input, &device, &opts, running)?;
+        }
+
+        Commands::List { all } => do_list(&scanner, all),
+
+        Commands::Doctor => do_doctor(),
+
+        Commands::Info { device, json } => {
+            let device = resolve_device(&scanner, device, None)?;
+            do_info(&device, json);
+        }
+
+        Commands::Verify {
+            input, device, against, hash, checkpoint, verify_bytes, write_checksum, diagnose_mismatch, keep_awake,
+        } => {
+            let input = match input {
+                Some(p) => {
+                    if !p.exists() { return Err(anyhow!("Image not found: {}", p.display())); }
+                    p
+                }
+                None => pick_file()?,
+            };
+            if let Some(other) = against {
+                if !other.exists() { return Err(anyhow!("Image not found: {}", other.display())); }
+                let opts = WriteOptions {
+                    hash,
+                    verify_bytes,
+                    diagnose_mismatch,
+                    keep_awake,
+                    dry_run: cli.dry_run,
+                    verbose: cli.verbose,
+                    ..Default::default()
+                };
+                let _awake = keep_awake_lock(&opts);
+                return do_compare(&input, &other, &opts, &phase_multi())
+                    .map_err(|e| fail(EXIT_VERIFY_FAILED, e));
+            }
+            // Like the wizard's verify: a smaller drive still gets its part checked
+            let device = resolve_device(&scanner, device, None)?;
+            if let Some(path) = &write_checksum {
+                check_checksum_file(path, cli.force)?;
+            }
+            let opts = WriteOptions {
+                hash,
+                checkpoint,
+                verify_bytes,
+                write_checksum,
+                diagnose_mismatch,
+                keep_awake,
+                dry_run: cli.dry_run,
+                verbose: cli.verbose,
+                ..Default::default()
+            };
+            let _awake = keep_awake_lock(&opts);
+            do_verify(&input, &device, (1, 1), None, &opts, &phase_multi(), running)
+                .map_err(|e| fail(EXIT_VERIFY_FAILED, e))?;
+        }
+
+        Commands::Wizard => {
+            let base = WriteOptions {
+                large_device_bytes: config.large_device_bytes,
+                force: cli.force,
+                dry_run: cli.dry_run,
+                verbose: cli.verbose,
+                ..Default::default()
+            };
+            do_wizard(&base, running)?;
+        }
+
+        Commands::Wipe { device, mode, allow_raid } => {
+            let device = resolve_device(&scanner, device, None)?;
+            let opts = WriteOptions {
+                allow_raid,
+                large_device_bytes: config.large_device_bytes,
+                force: cli.force,
+                dry_run: cli.dry_run,
+                verbose: cli.verbose,
+                ..Default::default()
+            };
+            if !wipe_confirm(&device, mode, &opts)? {
+                return Err(cancelled());
+            }
+            do_wipe(&device, mode, &opts, running)?;
+        }
+
+        Commands::Unmount { device } => {
+            let device = resolve_device(&scanner, device, None)?;
+            do_unmount(&device, cli.dry_run)?;
+        }
+
+        Commands::Completions { .. } => unreachable!("handled before the banner"),
+
+        Commands::Clone { device, output } => {
+            let device = resolve_device(&scanner, device, None)?;
+            do_clone(&device, &output, cli.force, cli.dry_run, running)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1_000_000;
+
+    fn plan(write_bytes: u64, device_sector_size: u64) -> WritePlan {
+        WritePlan {
+            source: "ubuntu.iso".into(),
+            source_bytes: write_bytes,
+            decompressor: None,
+            device: "/dev/sdb".into(),
+            device_model: "SanDisk Ultra".into(),
+            device_bytes: 16_000_000_000,
+            device_sector_size,
+            unmount: Vec::new(),
+            block_size: 4 * 1024 * 1024,
+            write_bytes,
+            range: None,
+            hash: "md5",
+            commands: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn sectors_for_512_and_4096_byte_sectors() {
+        assert_eq!(plan(1_000_001, 512).sectors(), 1954);
+        assert_eq!(plan(1_000_001, 4096).sectors(), 245);
+        // Exact multiples don't gain a sector
+        assert_eq!(plan(1 << 30, 512).sectors(), 2_097_152);
+        assert_eq!(plan(1 << 30, 4096).sectors(), 262_144);
+        assert_eq!(plan(1, 4096).sectors(), 1);
+    }
+
+    #[test]
+    fn a_30_mb_image_rounds_up_to_whole_sectors_and_blocks() {
+        let small = plan(30 * MB, 512);
+        assert_eq!(small.sectors(), 58_594);
+        assert_eq!(small.blocks(), 8);
+        assert_eq!(plan(30 * MB, 4096).sectors(), 7_325);
+    }
+
+    #[test]
+    fn rate_averages_over_the_window() {
+        let t0 = Instant::now();
+        let at = |s: u64| t0 + Duration::from_secs(s);
+        let mut rate = RateEstimator::new(Duration::from_secs(2));
+        assert_eq!(rate.rate(), None);
+        rate.push(at(0), 0);
+        assert_eq!(rate.rate(), None);
+        rate.push(at(1), 10 * MB);
+        assert_eq!(rate.rate(), Some(10.0 * MB as f64));
+        // A cache flush: nothing for a second, then a burst
+        rate.push(at(2), 10 * MB);
+        assert_eq!(rate.rate(), Some(5.0 * MB as f64));
+        rate.push(at(3), 40 * MB);
+        // The 0s sample fell out of the window; 1s..3s remain
+        assert_eq!(rate.rate(), Some(15.0 * MB as f64));
+        assert_eq!(rate.eta(30 * MB), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn rate_keeps_two_samples_after_a_long_stall() {
+        let t0 = Instant::now();
+        let mut rate = RateEstimator::new(Duration::from_secs(2));
+        rate.push(t0, 0);
+        rate.push(t0 + Duration::from_secs(60), 120 * MB);
+        assert_eq!(rate.rate(), Some(2.0 * MB as f64));
+    }
+
+    #[test]
+    fn no_eta_without_progress() {
+        let t0 = Instant::now();
+        let mut rate = RateEstimator::new(Duration::from_secs(2));
+        rate.push(t0, 5 * MB);
+        rate.push(t0 + Duration::from_secs(1), 5 * MB);
+        assert_eq!(rate.eta(MB), None);
+    }
+
+    #[test]
+    fn eta_formats() {
+        assert_eq!(format_eta(Duration::from_secs(42)), "42s");
+        assert_eq!(format_eta(Duration::from_secs(95)), "1m 35s");
+        assert_eq!(format_eta(Duration::from_secs(3 * 3600 + 600)), "3h 10m");
+    }
+}